@@ -1,6 +1,7 @@
-use std::{fs, io, thread, time};
+use std::{env, fs, io, thread, time};
 use std::os::fd::AsRawFd;
 use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
 use std::os::unix::io::{FromRawFd, OwnedFd};
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
@@ -14,6 +15,7 @@ use tokio::sync::Mutex;
 use tracing::{info, warn, instrument};
 
 use super::build;
+use super::nodes::{self, NodeState};
 use super::run_tests::cleanup::{cleanup, cleanup_on_signal};
 use super::run_tests::network_router;
 use super::run_tests::types::*;
@@ -22,6 +24,7 @@ const KINODE_RELEASE_BASE_URL: &str = "https://github.com/kinode-dao/kinode/rele
 pub const KINODE_OWNER: &str = "kinode-dao";
 const KINODE_REPO: &str = "kinode";
 const LOCAL_PREFIX: &str = "/tmp/kinode-";
+pub const NODE_LOG_FILE_NAME: &str = "kit-node.log";
 pub const CACHE_EXPIRY_SECONDS: u64 = 300;
 
 #[derive(Deserialize, Debug)]
@@ -322,6 +325,19 @@ async fn fetch_latest_release_tag_or_local(owner: &str, repo: &str) -> anyhow::R
     }
 }
 
+/// Inherit if `verbose`, else append to `log_path` if given, else discard
+fn make_runtime_stdio(verbose: bool, log_path: Option<&Path>) -> anyhow::Result<Stdio> {
+    if verbose {
+        return Ok(Stdio::inherit());
+    }
+    match log_path {
+        Some(log_path) => Ok(Stdio::from(
+            fs::OpenOptions::new().create(true).append(true).open(log_path)?
+        )),
+        None => Ok(Stdio::null()),
+    }
+}
+
 #[instrument(level = "trace", err, skip_all)]
 pub fn run_runtime(
     path: &Path,
@@ -331,7 +347,8 @@ pub fn run_runtime(
     args: &[&str],
     verbose: bool,
     detached: bool,
-) -> anyhow::Result<(Child, OwnedFd)> {
+    log_path: Option<&Path>,
+) -> anyhow::Result<(Child, OwnedFd, Option<PathBuf>)> {
     let port = format!("{}", port);
     let network_router_port = format!("{}", network_router_port);
     let mut full_args = vec![
@@ -344,36 +361,27 @@ pub fn run_runtime(
     }
 
     let fds = nix::pty::openpty(None, None)?;
+    let pty_path = nix::unistd::ttyname(fds.slave.as_raw_fd()).ok();
 
     let process = Command::new(path)
         .args(&full_args)
         .stdin(if !detached { Stdio::inherit() } else { unsafe { Stdio::from_raw_fd(fds.slave.as_raw_fd()) } })
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
+        .stdout(make_runtime_stdio(verbose, log_path)?)
+        .stderr(make_runtime_stdio(verbose, log_path)?)
         .spawn()?;
 
-    Ok((process, fds.master))
+    Ok((process, fds.master, pty_path))
 }
 
+/// Fetch the given runtime `version` or, if `runtime_path` is given, compile the repo there
 #[instrument(level = "trace", err, skip_all)]
-pub async fn execute(
+pub async fn get_runtime_path(
     runtime_path: Option<PathBuf>,
-    version: String,
-    node_home: PathBuf,
-    node_port: u16,
-    network_router_port: u16,
-    rpc: Option<&str>,
-    is_testnet: bool,
-    fake_node_name: &str,
-    password: &str,
-    is_persist: bool,
+    version: &str,
     release: bool,
-    mut args: Vec<&str>,
-) -> anyhow::Result<()> {
-    let detached = false;  // TODO: to argument?
-    // TODO: factor out with run_tests?
-    let runtime_path = match runtime_path {
-        None => get_runtime_binary(&version).await?,
+) -> anyhow::Result<PathBuf> {
+    match runtime_path {
+        None => get_runtime_binary(version).await,
         Some(runtime_path) => {
             if !runtime_path.exists() {
                 return Err(anyhow::anyhow!(
@@ -384,20 +392,229 @@ pub async fn execute(
             if runtime_path.is_dir() {
                 // Compile the runtime binary
                 compile_runtime(&runtime_path, true, release)?;
-                runtime_path.join("target")
+                Ok(runtime_path.join("target")
                     .join(if release { "release" } else { "debug" })
                     .join("kinode")
+                )
             } else {
-                return Err(anyhow::anyhow!(
+                Err(anyhow::anyhow!(
                     "--runtime-path {:?} must be a directory (the repo).",
                     runtime_path,
-                ));
+                ))
             }
         },
+    }
+}
+
+/// A `--topology` file: a shared network router plus named fake nodes
+#[derive(Deserialize, Debug)]
+struct Topology {
+    network_router: Option<NetworkRouter>,
+    nodes: Vec<TopologyNode>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TopologyNode {
+    name: String,
+    port: Option<u16>,
+    home: Option<PathBuf>,
+    password: Option<String>,
+    rpc: Option<String>,
+    #[serde(default)]
+    is_testnet: bool,
+}
+
+#[derive(Debug)]
+struct FakeNode {
+    name: String,
+    home: PathBuf,
+    port: u16,
+    password: String,
+    rpc: Option<String>,
+    is_testnet: bool,
+}
+
+/// Insert `index` before the `.os` suffix: `fake.os` -> `fake1.os`
+fn make_indexed_node_name(fake_node_name: &str, index: u16) -> String {
+    match fake_node_name.strip_suffix(".os") {
+        Some(stem) => format!("{}{}.os", stem, index),
+        None => format!("{}{}", fake_node_name, index),
+    }
+}
+
+#[instrument(level = "trace", err, skip_all)]
+fn make_fake_nodes(
+    topology: Option<&Topology>,
+    count: u16,
+    node_home: &Path,
+    node_port: u16,
+    rpc: Option<&str>,
+    is_testnet: bool,
+    fake_node_name: &str,
+    password: &str,
+) -> anyhow::Result<Vec<FakeNode>> {
+    let fake_nodes = match topology {
+        Some(topology) => topology.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| FakeNode {
+                name: node.name.clone(),
+                home: node.home.clone().unwrap_or_else(|| node_home.join(&node.name)),
+                port: node.port.unwrap_or(node_port + i as u16),
+                password: node.password.clone().unwrap_or_else(|| password.to_string()),
+                rpc: node.rpc.clone().or_else(|| rpc.map(|r| r.to_string())),
+                is_testnet: node.is_testnet || is_testnet,
+            })
+            .collect(),
+        None if count == 1 => vec![FakeNode {
+            name: fake_node_name.to_string(),
+            home: node_home.to_path_buf(),
+            port: node_port,
+            password: password.to_string(),
+            rpc: rpc.map(|r| r.to_string()),
+            is_testnet,
+        }],
+        None => (0..count)
+            .map(|i| {
+                let name = make_indexed_node_name(fake_node_name, i + 1);
+                FakeNode {
+                    home: node_home.join(&name),
+                    name,
+                    port: node_port + i,
+                    password: password.to_string(),
+                    rpc: rpc.map(|r| r.to_string()),
+                    is_testnet,
+                }
+            })
+            .collect(),
     };
+    if fake_nodes.is_empty() {
+        return Err(anyhow::anyhow!("kit boot-fake-node: no nodes to boot"));
+    }
+    Ok(fake_nodes)
+}
+
+/// Re-run this `kit boot-fake-node` in the background as the nodes' supervisor
+///  and return once it has booted them all
+#[instrument(level = "trace", err, skip_all)]
+async fn boot_detached(fake_nodes: &[FakeNode]) -> anyhow::Result<()> {
+    for fake_node in fake_nodes {
+        if let Some(state) = nodes::read_state(&fake_node.name)? {
+            if state.is_running() {
+                return Err(anyhow::anyhow!(
+                    "kit boot-fake-node: {} is already running (pid {}); `kit nodes stop {}` first",
+                    fake_node.name,
+                    state.pid,
+                    fake_node.name,
+                ));
+            }
+        }
+    }
+
+    let supervisor_log_path = nodes::get_nodes_dir()?
+        .join(format!("{}.supervisor.log", fake_nodes[0].name));
+    let supervisor_log = fs::File::create(&supervisor_log_path)?;
+    let args: Vec<String> = env::args()
+        .skip(1)
+        .filter(|arg| arg != "--detach" && arg != "-d")
+        .collect();
+    let mut supervisor = Command::new(env::current_exe()?)
+        .args(&args)
+        .arg("--background")
+        .stdin(Stdio::null())
+        .stdout(supervisor_log.try_clone()?)
+        .stderr(supervisor_log)
+        .process_group(0)
+        .spawn()?;
+
+    loop {
+        if let Some(status) = supervisor.try_wait()? {
+            return Err(anyhow::anyhow!(
+                "kit boot-fake-node: background nodes exited early ({}); see {:?}",
+                status,
+                supervisor_log_path,
+            ));
+        }
+        let mut states = Vec::new();
+        for fake_node in fake_nodes {
+            match nodes::read_state(&fake_node.name)? {
+                Some(state) if state.supervisor_pid == supervisor.id() => states.push(state),
+                _ => break,
+            }
+        }
+        if states.len() == fake_nodes.len() {
+            for state in states {
+                info!(
+                    "{} running in background on port {} (pid {}; logs {:?})",
+                    state.name,
+                    state.port,
+                    state.pid,
+                    state.log_path,
+                );
+            }
+            info!("Use `kit nodes list|stop|logs` to manage them.");
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub async fn execute(
+    runtime_path: Option<PathBuf>,
+    version: String,
+    node_home: PathBuf,
+    node_port: u16,
+    network_router_port: u16,
+    rpc: Option<&str>,
+    is_testnet: bool,
+    fake_node_name: &str,
+    password: &str,
+    is_persist: bool,
+    release: bool,
+    record: Option<PathBuf>,
+    topology_path: Option<PathBuf>,
+    count: u16,
+    detach: bool,
+    background: bool,
+    args: Vec<&str>,
+) -> anyhow::Result<()> {
+    let topology: Option<Topology> = match topology_path {
+        Some(ref topology_path) => Some(toml::from_str(&fs::read_to_string(topology_path)?)?),
+        None => None,
+    };
+    let fake_nodes = make_fake_nodes(
+        topology.as_ref(),
+        count,
+        &node_home,
+        node_port,
+        rpc,
+        is_testnet,
+        fake_node_name,
+        password,
+    )?;
+    // The supervisor inherits `--detach` (e.g. in combined short flags), so
+    //  `background` takes precedence
+    if detach && !background {
+        return boot_detached(&fake_nodes).await;
+    }
+    let mut network_router = topology
+        .and_then(|t| t.network_router)
+        .unwrap_or_else(|| NetworkRouter::new(network_router_port));
+    if network_router.record.is_none() {
+        network_router.record = record;
+    }
+    let network_router_port = network_router.port;
+
+    // A single node is attached to this terminal; multiple nodes, or
+    //  any nodes in the background, have output sent to log files
+    let detached = fake_nodes.len() > 1 || background;
+    // TODO: factor out with run_tests?
+    let runtime_path = get_runtime_path(runtime_path, &version, release).await?;
 
     let mut task_handles = Vec::new();
 
+    let node_handles = Arc::new(Mutex::new(Vec::new()));
     let node_cleanup_infos = Arc::new(Mutex::new(Vec::new()));
 
     let (send_to_cleanup, recv_in_cleanup) = tokio::sync::mpsc::unbounded_channel();
@@ -406,11 +623,12 @@ pub async fn execute(
     let recv_kill_in_router = send_to_kill.subscribe();
 
     let node_cleanup_infos_for_cleanup = Arc::clone(&node_cleanup_infos);
+    let node_handles_for_cleanup = if detached { Some(Arc::clone(&node_handles)) } else { None };
     let handle = tokio::spawn(cleanup(
         recv_in_cleanup,
         send_to_kill,
         node_cleanup_infos_for_cleanup,
-        None,
+        node_handles_for_cleanup,
         detached,
         !is_persist,
     ));
@@ -421,52 +639,121 @@ pub async fn execute(
     let send_to_cleanup_for_cleanup = send_to_cleanup.clone();
     let _cleanup_context = CleanupContext::new(send_to_cleanup_for_cleanup);
 
-    let network_router_port_for_router = network_router_port.clone();
     let handle = tokio::spawn(async move {
         let _ = network_router::execute(
-            network_router_port_for_router,
-            NetworkRouterDefects::None,
+            network_router,
             recv_kill_in_router,
         ).await;
     });
     task_handles.push(handle);
 
-    if node_home.exists() {
-        fs::remove_dir_all(&node_home)?;
+    for fake_node in &fake_nodes {
+        if fake_node.home.exists() {
+            fs::remove_dir_all(&fake_node.home)?;
+        }
     }
 
     // TODO: can remove?
     thread::sleep(time::Duration::from_secs(1));
 
-    if let Some(ref rpc) = rpc {
-        args.extend_from_slice(&["--rpc", rpc]);
-    };
-    args.extend_from_slice(&["--fake-node-name", fake_node_name]);
-    args.extend_from_slice(&["--password", password]);
-    if is_testnet {
-        args.push("--testnet");
-    }
+    let mut runtime_processes = Vec::new();
+    for fake_node in &fake_nodes {
+        let mut node_args = args.clone();
+        if let Some(ref rpc) = fake_node.rpc {
+            node_args.extend_from_slice(&["--rpc", rpc]);
+        };
+        node_args.extend_from_slice(&["--fake-node-name", &fake_node.name]);
+        node_args.extend_from_slice(&["--password", &fake_node.password]);
+        if fake_node.is_testnet {
+            node_args.push("--testnet");
+        }
 
-    let (mut runtime_process, master_fd) = run_runtime(
-        &runtime_path,
-        &node_home,
-        node_port,
-        network_router_port,
-        &args[..],
-        true,
-        detached,
-    )?;
+        let log_path = if detached {
+            fs::create_dir_all(&fake_node.home)?;
+            Some(fake_node.home.join(NODE_LOG_FILE_NAME))
+        } else {
+            None
+        };
 
-    let mut node_cleanup_infos = node_cleanup_infos.lock().await;
-    node_cleanup_infos.push(NodeCleanupInfo {
-        master_fd,
-        process_id: runtime_process.id() as i32,
-        home: node_home.clone(),
-    });
-    drop(node_cleanup_infos);
+        let (runtime_process, master_fd, pty_path) = run_runtime(
+            &runtime_path,
+            &fake_node.home,
+            fake_node.port,
+            network_router_port,
+            &node_args[..],
+            !detached,
+            detached,
+            log_path.as_deref(),
+        )?;
+
+        let mut node_cleanup_infos = node_cleanup_infos.lock().await;
+        node_cleanup_infos.push(NodeCleanupInfo {
+            master_fd,
+            process_id: runtime_process.id() as i32,
+            home: fake_node.home.clone(),
+        });
+        drop(node_cleanup_infos);
+
+        if background {
+            nodes::write_state(&NodeState {
+                name: fake_node.name.clone(),
+                pid: runtime_process.id(),
+                port: fake_node.port,
+                home: fake_node.home.clone(),
+                log_path: fake_node.home.join(NODE_LOG_FILE_NAME),
+                pty_path,
+                network_router_port,
+                supervisor_pid: std::process::id(),
+            })?;
+        }
+        if let Some(log_path) = log_path {
+            info!(
+                "Booted {} on port {} (home {:?}; logs {:?})",
+                fake_node.name,
+                fake_node.port,
+                fake_node.home,
+                log_path,
+            );
+        }
+        runtime_processes.push(runtime_process);
+    }
 
-    runtime_process.wait().unwrap();
-    let _ = send_to_cleanup.send(true);
+    if !detached {
+        runtime_processes[0].wait().unwrap();
+        let _ = send_to_cleanup.send(true);
+    } else if background {
+        // Nodes are stopped individually by `kit nodes stop`:
+        //  clean up once they have all exited
+        node_handles.lock().await.append(&mut runtime_processes);
+        let mut is_exiteds = vec![false; fake_nodes.len()];
+        while is_exiteds.iter().any(|is_exited| !is_exited) {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            let mut node_handles = node_handles.lock().await;
+            let node_cleanup_infos = node_cleanup_infos.lock().await;
+            let nodes = fake_nodes.iter().zip(node_handles.iter_mut()).zip(node_cleanup_infos.iter());
+            for (i, ((fake_node, node_handle), cleanup_info)) in nodes.enumerate() {
+                if is_exiteds[i] {
+                    continue;
+                }
+                if node_handle.try_wait()?.is_some() {
+                    is_exiteds[i] = true;
+                    nodes::remove_own_state(&fake_node.name, node_handle.id())?;
+                } else if nodes::take_stop_request(&fake_node.name, node_handle.id())? {
+                    info!("Stopping {}...", fake_node.name);
+                    // Ignore failure: the node may have just exited
+                    let _ = nix::unistd::write(cleanup_info.master_fd.as_raw_fd(), b"\x03");
+                }
+            }
+        }
+        let _ = send_to_cleanup.send(true);
+    } else {
+        info!(
+            "{} fake nodes running with network router on port {}; Ctrl+C to exit.",
+            fake_nodes.len(),
+            network_router_port,
+        );
+        node_handles.lock().await.append(&mut runtime_processes);
+    }
     for handle in task_handles {
         handle.await.unwrap();
     }
@@ -0,0 +1,213 @@
+use regex::Regex;
+use serde::Serialize;
+
+use super::ProcessSizes;
+
+/// Most lines of captured output to add to the error of a failed command
+const MAX_FAILURE_LINES: usize = 40;
+/// Lines of captured output to add if no line looks like an error
+const FAILURE_TAIL_LINES: usize = 20;
+
+/// How `kit build` reports progress: human-readable logs, or one JSON
+///  object per line on stdout for editors & CI (logs then go to stderr)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl std::str::FromStr for MessageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(anyhow::anyhow!("kit build: message format must be 'human' or 'json'; not '{s}'")),
+        }
+    }
+}
+
+impl MessageFormat {
+    /// Print `event` as a line of JSON, if so configured
+    pub fn emit(&self, event: &BuildEvent) {
+        if *self != MessageFormat::Json {
+            return;
+        }
+        match serde_json::to_string(event) {
+            Ok(event) => println!("{}", event),
+            Err(e) => tracing::warn!("kit build: couldn't serialize build event: {}", e),
+        }
+    }
+}
+
+/// A step of a build starting, finishing or failing
+#[derive(Debug, Serialize)]
+pub struct BuildEvent {
+    /// `start`, `finish` or `error`
+    pub(super) event: &'static str,
+    /// `process`, `ui`, or `command` (a toolchain command run by either)
+    pub(super) step: &'static str,
+    /// The process (as `package/process` if building several packages) or
+    ///  the package whose UI is built
+    pub(super) name: String,
+    /// The toolchain command, for `command` events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) duration_ms: Option<u128>,
+    /// Unchanged since last built, so not rebuilt
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(super) skipped: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) sizes: Option<ProcessSizes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) message: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) diagnostics: Vec<Diagnostic>,
+}
+
+impl BuildEvent {
+    pub fn new(event: &'static str, step: &'static str, name: &str) -> Self {
+        BuildEvent {
+            event,
+            step,
+            name: name.to_string(),
+            command: None,
+            duration_ms: None,
+            skipped: false,
+            sizes: None,
+            message: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// An `error` event for `error`, with the diagnostics found in `output`
+    pub fn error(step: &'static str, name: &str, error: &anyhow::Error, output: &str) -> Self {
+        BuildEvent {
+            message: Some(format!("{:#}", error)),
+            diagnostics: parse_diagnostics(output),
+            ..BuildEvent::new("error", step, name)
+        }
+    }
+}
+
+/// An error or warning reported by a toolchain
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    /// `error` or `warning`
+    pub level: String,
+    pub message: String,
+    /// As reported by the toolchain: usually relative to the process dir
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+}
+
+/// Errors & warnings, with their locations, in the output of rustc (via
+///  cargo), tsc and Python (componentize-py) builds
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    // error[E0425]: cannot find value `x` in this scope
+    //   --> src/lib.rs:10:5
+    let rust_header = Regex::new(r"^(error|warning)(\[\w+\])?: (.+)$").unwrap();
+    let rust_location = Regex::new(r"^\s*--> (.+):(\d+):(\d+)$").unwrap();
+    // src/lib.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.
+    let typescript = Regex::new(r"^(.+)\((\d+),(\d+)\): (error|warning) (TS\d+: .+)$").unwrap();
+    //   File "/path/to/lib.py", line 12, in init
+    // NameError: name 'x' is not defined
+    let python_location = Regex::new(r#"^\s*File "(.+)", line (\d+)"#).unwrap();
+    let python_error = Regex::new(r"^(\w+(Error|Exception)): (.+)$").unwrap();
+
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    // Whether the last diagnostic is a Rust one still waiting for its location
+    let mut is_rust_header_last = false;
+    let mut python_last_location: Option<(String, u32)> = None;
+    for line in output.lines() {
+        if let Some(captures) = rust_header.captures(line) {
+            let message = captures[3].to_string();
+            // Summaries repeat the errors above them
+            if message.starts_with("could not compile") || message.starts_with("aborting due to") {
+                is_rust_header_last = false;
+                continue;
+            }
+            diagnostics.push(Diagnostic {
+                level: captures[1].to_string(),
+                message,
+                file: None,
+                line: None,
+                column: None,
+            });
+            is_rust_header_last = true;
+        } else if let Some(captures) = rust_location.captures(line) {
+            if is_rust_header_last {
+                if let Some(diagnostic) = diagnostics.last_mut() {
+                    diagnostic.file = Some(captures[1].to_string());
+                    diagnostic.line = captures[2].parse().ok();
+                    diagnostic.column = captures[3].parse().ok();
+                }
+            }
+            is_rust_header_last = false;
+        } else if let Some(captures) = typescript.captures(line) {
+            diagnostics.push(Diagnostic {
+                level: captures[4].to_string(),
+                message: captures[5].to_string(),
+                file: Some(captures[1].to_string()),
+                line: captures[2].parse().ok(),
+                column: captures[3].parse().ok(),
+            });
+        } else if let Some(captures) = python_location.captures(line) {
+            python_last_location = captures[2]
+                .parse()
+                .ok()
+                .map(|line| (captures[1].to_string(), line));
+        } else if let Some(captures) = python_error.captures(line) {
+            let (file, line) = python_last_location.take().unzip();
+            diagnostics.push(Diagnostic {
+                level: "error".to_string(),
+                message: format!("{}: {}", &captures[1], &captures[3]),
+                file,
+                line,
+                column: None,
+            });
+        }
+    }
+    // Warnings are only worth reporting where they can be shown
+    diagnostics.retain(|d| d.level == "error" || d.file.is_some());
+    diagnostics
+}
+
+fn is_error_line(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("error")
+        || line.starts_with("Error")
+        || line.starts_with("Traceback")
+        || line.starts_with("npm ERR!")
+        || line.contains("): error TS")
+}
+
+/// The part of a failed command's `output` that explains the failure: from
+///  the first error onwards, or else its last lines
+pub fn get_failure_excerpt(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| is_error_line(line))
+        .unwrap_or_else(|| lines.len().saturating_sub(FAILURE_TAIL_LINES));
+    let mut excerpt = lines[start..]
+        .iter()
+        .take(MAX_FAILURE_LINES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if lines.len() - start > MAX_FAILURE_LINES {
+        excerpt.push_str(&format!(
+            "\n... ({} more lines)",
+            lines.len() - start - MAX_FAILURE_LINES,
+        ));
+    }
+    excerpt
+}
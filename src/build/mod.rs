@@ -1,18 +1,55 @@
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::time::Instant;
 
+use futures_util::StreamExt;
+use nix::fcntl::{flock, FlockArg};
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
 use tokio::fs;
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
+use walkdir::WalkDir;
 
-use super::setup::{check_js_deps, check_py_deps, check_rust_deps, get_deps, get_newest_valid_node_version, REQUIRED_PY_PACKAGE};
+use super::check;
+use super::config;
+pub use diagnostics::MessageFormat;
+use diagnostics::{get_failure_excerpt, BuildEvent};
 
+use super::setup::{check_js_deps, check_py_deps, check_rust_deps, get_deps, get_newest_valid_node_version, is_command_installed, REQUIRED_PY_PACKAGE};
+
+mod diagnostics;
+
+/// Venv that earlier versions of kit created in each Python process dir
 const PY_VENV_NAME: &str = "process_env";
+/// Shared venvs holding `componentize-py`, under `CACHE_DIR`
+const PY_VENVS_DIR: &str = "python-venvs";
+/// Written into a shared venv once `componentize-py` is installed in it
+const PY_VENV_READY_FILE: &str = ".kit-ready";
+/// Pure-Python dependencies of a process, vendored into its component
+const PYTHON_REQUIREMENTS_PATH: &str = "src/requirements.txt";
+/// Where the dependencies in `PYTHON_REQUIREMENTS_PATH` are installed
+const PYTHON_DEPS_PATH: &str = "target/python-deps";
+/// Written into `PYTHON_DEPS_PATH` with the hash of the requirements installed there
+const PYTHON_DEPS_HASH_FILE: &str = ".kit-requirements";
+const PYTHON_WHEELHOUSE_ENV_VAR: &str = "KIT_PYTHON_WHEELHOUSE";
 const JAVASCRIPT_SRC_PATH: &str = "src/lib.js";
 const PYTHON_SRC_PATH: &str = "src/lib.py";
 const RUST_SRC_PATH: &str = "src/lib.rs";
-const KINODE_WIT_URL: &str = "https://raw.githubusercontent.com/kinode-dao/kinode-wit/v0.7.0-alpha/kinode.wit";
+const TYPESCRIPT_SRC_PATH: &str = "src/lib.ts";
+/// Where `tsc` writes the transpiled process, per the template `tsconfig.json`
+const TYPESCRIPT_OUT_PATH: &str = "target/lib.js";
+const KINODE_WIT_VERSION: &str = "0.7.0-alpha";
+const WASI_VERSION: &str = "15.0.1";
+const MIRROR_DIR_ENV_VAR: &str = "KIT_MIRROR_DIR";
+const FINGERPRINT_PATH: &str = "target/kit-fingerprint";
+pub const BUILD_INFO_PATH: &str = "pkg/build-info.json";
+/// Process subdirectories that are build products rather than sources
+pub const FINGERPRINT_IGNORED_DIRS: [&str; 3] = ["target", "node_modules", PY_VENV_NAME];
 pub const CACHE_DIR: &str = "/tmp/kinode-kit-cache";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,16 +62,485 @@ struct CargoPackage {
     name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Metadata {
-    package: String,
-    publisher: String,
-    version: [u32; 3],
+/// Where `kit build` gets the kinode WIT & WASI adapter it adds to
+///  processes: the `kit` object of `pkg/metadata.json`, falling back to
+///  `[build]` in kit config. `wit` & `wasi_adapter` are URLs or paths
+///  (relative to the package dir) and override the corresponding version
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuildDependencies {
+    pub wit_version: Option<String>,
+    pub wit: Option<String>,
+    pub wasi_version: Option<String>,
+    pub wasi_adapter: Option<String>,
+}
+
+/// The part of `pkg/metadata.json` that `kit build` reads
+#[derive(Debug, Deserialize)]
+struct BuildMetadata {
+    #[serde(default)]
+    kit: BuildDependencies,
+}
+
+/// How a process is built, determined by which source file it has
+#[derive(Debug, Clone)]
+enum ProcessLanguage {
+    Rust,
+    Python,
+    Javascript,
+    Typescript,
+    /// A backend registered in `[build.backends]` of kit config
+    Custom(String, config::BuildBackend),
+}
+
+impl ProcessLanguage {
+    /// Built-in languages take precedence over registered backends
+    fn detect(
+        process_dir: &Path,
+        backends: &BTreeMap<String, config::BuildBackend>,
+    ) -> Option<ProcessLanguage> {
+        if process_dir.join(RUST_SRC_PATH).exists() {
+            Some(ProcessLanguage::Rust)
+        } else if process_dir.join(PYTHON_SRC_PATH).exists() {
+            Some(ProcessLanguage::Python)
+        } else if process_dir.join(JAVASCRIPT_SRC_PATH).exists() {
+            Some(ProcessLanguage::Javascript)
+        } else if process_dir.join(TYPESCRIPT_SRC_PATH).exists() {
+            Some(ProcessLanguage::Typescript)
+        } else {
+            backends
+                .iter()
+                .find(|(_, backend)| process_dir.join(&backend.src).exists())
+                .map(|(name, backend)| ProcessLanguage::Custom(name.clone(), backend.clone()))
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            ProcessLanguage::Rust => "rust",
+            ProcessLanguage::Python => "python",
+            ProcessLanguage::Javascript => "javascript",
+            ProcessLanguage::Typescript => "typescript",
+            ProcessLanguage::Custom(name, _) => name,
+        }
+    }
+
+    fn is_node(&self) -> bool {
+        matches!(self, ProcessLanguage::Javascript | ProcessLanguage::Typescript)
+    }
+}
+
+/// Compiler settings & post-processing for Rust processes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Profile {
+    /// Unoptimized, with debug info
+    Debug,
+    #[default]
+    Release,
+    /// Optimized for size, stripped & run through `wasm-opt -Oz`
+    Size,
+}
+
+impl Profile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Profile::Debug => "debug",
+            Profile::Release => "release",
+            Profile::Size => "size",
+        }
+    }
+}
+
+impl std::str::FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "debug" => Ok(Profile::Debug),
+            "release" => Ok(Profile::Release),
+            "size" => Ok(Profile::Size),
+            _ => Err(anyhow::anyhow!("kit build: profile must be 'debug', 'release' or 'size'; not '{s}'")),
+        }
+    }
+}
+
+/// How `kit build` builds processes
+#[derive(Debug, Clone, Copy)]
+pub struct BuildOptions {
+    /// Rebuild processes even if unchanged
+    pub force: bool,
+    /// Number of processes to build in parallel
+    pub jobs: usize,
+    pub profile: Profile,
+    /// Run `wasm-opt` on Rust process modules (always done for `Profile::Size`)
+    pub wasm_opt: bool,
+    /// Strip debug info from Rust process modules (always done for `Profile::Size`)
+    pub strip: bool,
+    pub message_format: MessageFormat,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            force: false,
+            jobs: default_jobs(),
+            profile: Profile::default(),
+            wasm_opt: false,
+            strip: false,
+            message_format: MessageFormat::default(),
+        }
+    }
+}
+
+impl BuildOptions {
+    fn is_wasm_opt(&self) -> bool {
+        self.wasm_opt || self.profile == Profile::Size
+    }
+
+    fn is_strip(&self) -> bool {
+        self.strip || self.profile == Profile::Size
+    }
+}
+
+/// Sizes in bytes of a process' build products, where known
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+struct ProcessSizes {
+    /// As compiled
+    module: Option<u64>,
+    /// After stripping, optimization & adaptation to a component
+    component: Option<u64>,
+    /// In `pkg/`, after post-build hooks
+    packaged: Option<u64>,
+}
+
+fn get_file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+fn format_size(size: Option<u64>) -> String {
+    match size {
+        None => "-".to_string(),
+        Some(size) if size >= 1 << 20 => format!("{:.2} MiB", size as f64 / (1 << 20) as f64),
+        Some(size) if size >= 1 << 10 => format!("{:.1} KiB", size as f64 / (1 << 10) as f64),
+        Some(size) => format!("{} B", size),
+    }
+}
+
+/// `pkg/build-info.json`: what a package's processes were built from &
+///  their hashes, to check that an installed package matches its sources.
+///  It is zipped with the package, so holds nothing (e.g. commits) that
+///  would change the zip of identical sources: see `SourceInfo`
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub kit_version: String,
+    /// URL (which includes the version) of `kinode.wit`, or SHA-256 of a
+    ///  local one, so that the file doesn't depend on where it was built
+    pub kinode_wit: String,
+    /// As `kinode_wit`, for the WASI adapter
+    pub wasi_adapter: String,
+    /// Process name -> how it was built
+    pub processes: BTreeMap<String, ProcessBuildInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcessBuildInfo {
+    pub language: String,
+    /// Relative to `pkg/`
+    pub wasm_path: String,
+    pub sha256: String,
+    /// Versions of everything used to build it other than its sources
+    pub toolchain: Vec<String>,
+}
+
+impl BuildInfo {
+    fn new(sources: &BuildSources) -> Self {
+        BuildInfo {
+            kit_version: env!("CARGO_PKG_VERSION").to_string(),
+            kinode_wit: get_source_version(&sources.kinode_wit),
+            wasi_adapter: get_source_version(&sources.wasi_adapter),
+            processes: BTreeMap::new(),
+        }
+    }
+
+    fn add_process(&mut self, process: &ProcessBuild) {
+        self.processes.insert(process.process_name(), ProcessBuildInfo {
+            language: process.language.name().to_string(),
+            wasm_path: format!("{}.wasm", process.process_name()),
+            sha256: String::new(),
+            toolchain: process.toolchain.lines().map(str::to_string).collect(),
+        });
+    }
+
+    /// Hash the built processes & write to `pkg/build-info.json`
+    fn write(mut self, package_dir: &Path) -> anyhow::Result<()> {
+        for process in self.processes.values_mut() {
+            let wasm = std::fs::read(package_dir.join("pkg").join(&process.wasm_path))?;
+            process.sha256 = hex::encode(Sha256::digest(wasm));
+        }
+        std::fs::write(
+            package_dir.join(BUILD_INFO_PATH),
+            format!("{}\n", serde_json::to_string_pretty(&self)?),
+        )?;
+        Ok(())
+    }
+}
+
+/// The commits a package was built from, written beside its zip rather than
+///  into it
+#[derive(Debug, Serialize)]
+pub struct SourceInfo {
+    pub kit_commit: String,
+    /// `HEAD` of the git repo holding the package, if any
+    pub source_commit: Option<String>,
+    /// Whether the package had uncommitted changes, other than kit's outputs
+    pub source_dirty: bool,
+}
+
+impl SourceInfo {
+    pub fn new(package_dir: &Path) -> Self {
+        let git = |args: &[&str]| get_command_output(Command::new("git").args(args).current_dir(package_dir));
+        let source_commit = git(&["rev-parse", "HEAD"]);
+        let status = git(&[
+            "status",
+            "--porcelain",
+            "--",
+            ".",
+            ":(exclude,glob)**/target/**",
+            ":(exclude,glob)**/node_modules/**",
+            ":(exclude)pkg/*.wasm",
+            ":(exclude)pkg/ui",
+            ":(exclude)pkg/build-info.json",
+        ]);
+        SourceInfo {
+            kit_commit: env!("GIT_COMMIT_SHA").to_string(),
+            source_dirty: !source_commit.is_empty() && !status.is_empty(),
+            source_commit: Some(source_commit).filter(|commit| !commit.is_empty()),
+        }
+    }
+}
+
+/// A process to build and how to build it
+#[derive(Debug)]
+struct ProcessBuild {
+    package_dir: PathBuf,
+    process_dir: PathBuf,
+    language: ProcessLanguage,
+    sources: BuildSources,
+    hooks: config::BuildHooks,
+    options: BuildOptions,
+    /// From `get_toolchain_version`, once dependencies are checked
+    toolchain: String,
+}
+
+impl ProcessBuild {
+    fn process_name(&self) -> String {
+        self.process_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// `package/process` if building several packages, else `process`
+    fn display_name(&self, is_many_packages: bool) -> String {
+        if is_many_packages {
+            format!(
+                "{}/{}",
+                self.package_dir.file_name().unwrap_or_default().to_string_lossy(),
+                self.process_name(),
+            )
+        } else {
+            self.process_name()
+        }
+    }
+
+    fn wasm_path(&self) -> PathBuf {
+        self.package_dir.join("pkg").join(format!("{}.wasm", self.process_name()))
+    }
+}
+
+/// `BuildDependencies` resolved to URLs or absolute paths
+#[derive(Debug, Clone)]
+struct BuildSources {
+    kinode_wit: String,
+    wasi_adapter: String,
+    mirror_dir: Option<PathBuf>,
+    /// Wheels to install Python packages from, rather than PyPI
+    python_wheelhouse: Option<PathBuf>,
+}
+
+impl BuildDependencies {
+    /// Fields set in `self` take precedence over those in `fallback`
+    fn or(self, fallback: BuildDependencies) -> BuildDependencies {
+        BuildDependencies {
+            wit_version: self.wit_version.or(fallback.wit_version),
+            wit: self.wit.or(fallback.wit),
+            wasi_version: self.wasi_version.or(fallback.wasi_version),
+            wasi_adapter: self.wasi_adapter.or(fallback.wasi_adapter),
+        }
+    }
+
+    fn resolve(
+        &self,
+        package_dir: &Path,
+        mirror_dir: Option<PathBuf>,
+        python_wheelhouse: Option<PathBuf>,
+    ) -> BuildSources {
+        let resolve_source = |source: &str| {
+            if is_url(source) {
+                source.to_string()
+            } else {
+                package_dir.join(source).to_string_lossy().to_string()
+            }
+        };
+        let wit_version = self.wit_version.as_deref().unwrap_or(KINODE_WIT_VERSION);
+        let wasi_version = self.wasi_version.as_deref().unwrap_or(WASI_VERSION);
+        BuildSources {
+            kinode_wit: self.wit.as_deref().map(resolve_source).unwrap_or_else(|| format!(
+                "https://raw.githubusercontent.com/kinode-dao/kinode-wit/v{}/kinode.wit",
+                wit_version.trim_start_matches('v'),
+            )),
+            wasi_adapter: self.wasi_adapter.as_deref().map(resolve_source).unwrap_or_else(|| format!(
+                "https://github.com/bytecodealliance/wasmtime/releases/download/v{}/wasi_snapshot_preview1.reactor.wasm",
+                wasi_version.trim_start_matches('v'),
+            )),
+            mirror_dir,
+            python_wheelhouse,
+        }
+    }
 }
 
 #[instrument(level = "trace", err, skip_all)]
-pub fn run_command(cmd: &mut Command) -> anyhow::Result<()> {
-    let status = cmd.status()?;
+fn read_build_dependencies(package_dir: &Path) -> anyhow::Result<BuildDependencies> {
+    let metadata_path = package_dir.join("pkg").join("metadata.json");
+    if !metadata_path.exists() {
+        return Ok(BuildDependencies::default());
+    }
+    let metadata: BuildMetadata = serde_json::from_str(&std::fs::read_to_string(&metadata_path)?)
+        .map_err(|e| anyhow::anyhow!("kit build: couldn't read {:?}: {}", metadata_path, e))?;
+    Ok(metadata.kit)
+}
+
+fn is_url(source: &str) -> bool {
+    source.contains("://")
+}
+
+/// Where the output of the commands building a process goes: captured
+///  output is kept to explain failures and, if `is_printed`, printed all at
+///  once so that concurrent builds don't interleave
+enum OutputSink {
+    Inherit,
+    Capture { buffer: Mutex<Vec<u8>>, is_printed: bool },
+}
+
+/// The commands run to build the process or UI `name`: their output and,
+///  per `message_format`, a `command` build event as each starts & finishes
+struct BuildOutput {
+    sink: OutputSink,
+    message_format: MessageFormat,
+    name: String,
+}
+
+impl BuildOutput {
+    fn new(verbose: bool, is_concurrent: bool, message_format: MessageFormat, name: &str) -> Self {
+        let sink = if verbose && !is_concurrent && message_format == MessageFormat::Human {
+            OutputSink::Inherit
+        } else {
+            OutputSink::Capture {
+                buffer: Mutex::new(Vec::new()),
+                is_printed: verbose && message_format == MessageFormat::Human,
+            }
+        };
+        BuildOutput { sink, message_format, name: name.to_string() }
+    }
+
+    /// Run `cmd`; if its output is captured but not printed, the error on
+    ///  failure includes the lines explaining it
+    fn run(&self, cmd: &mut Command) -> anyhow::Result<()> {
+        let command = format_command(cmd);
+        self.message_format.emit(&BuildEvent {
+            command: Some(command.clone()),
+            ..BuildEvent::new("start", "command", &self.name)
+        });
+        let start = Instant::now();
+        let (result, command_output) = match self.sink {
+            OutputSink::Inherit => (
+                run_command(cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit())),
+                String::new(),
+            ),
+            OutputSink::Capture { ref buffer, is_printed } => match cmd
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output()
+            {
+                Err(e) => (Err(e.into()), String::new()),
+                Ok(output) => {
+                    let mut command_output = output.stdout;
+                    command_output.extend_from_slice(&output.stderr);
+                    buffer.lock().unwrap().extend_from_slice(&command_output);
+                    let command_output = String::from_utf8_lossy(&command_output).to_string();
+                    let result = check_status(cmd, output.status).map_err(|e| {
+                        let excerpt = get_failure_excerpt(&command_output);
+                        if is_printed || excerpt.is_empty() {
+                            e
+                        } else {
+                            anyhow::anyhow!("{}; output:\n{}", e, excerpt)
+                        }
+                    });
+                    (result, command_output)
+                },
+            },
+        };
+        self.message_format.emit(&match result {
+            Ok(()) => BuildEvent {
+                duration_ms: Some(start.elapsed().as_millis()),
+                command: Some(command),
+                ..BuildEvent::new("finish", "command", &self.name)
+            },
+            Err(ref e) => BuildEvent {
+                duration_ms: Some(start.elapsed().as_millis()),
+                command: Some(command),
+                ..BuildEvent::error("command", &self.name, e, &command_output)
+            },
+        });
+        result
+    }
+
+    /// All captured output
+    fn captured(&self) -> String {
+        match self.sink {
+            OutputSink::Inherit => String::new(),
+            OutputSink::Capture { ref buffer, .. } => {
+                String::from_utf8_lossy(&buffer.lock().unwrap()).to_string()
+            },
+        }
+    }
+
+    /// Print captured output, if so configured, each line prefixed with `[name]`
+    fn print(&self) -> anyhow::Result<()> {
+        let OutputSink::Capture { ref buffer, is_printed: true } = self.sink else {
+            return Ok(());
+        };
+        let buffer = buffer.lock().unwrap();
+        let mut prefixed = String::new();
+        for line in String::from_utf8_lossy(&buffer).lines() {
+            prefixed.push_str(&format!("[{}] {}\n", self.name, line));
+        }
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(prefixed.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// `cmd`'s program & args, as for a shell
+fn format_command(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn check_status(cmd: &Command, status: ExitStatus) -> anyhow::Result<()> {
     if status.success() {
         Ok(())
     } else {
@@ -47,6 +553,12 @@ pub fn run_command(cmd: &mut Command) -> anyhow::Result<()> {
     }
 }
 
+#[instrument(level = "trace", err, skip_all)]
+pub fn run_command(cmd: &mut Command) -> anyhow::Result<()> {
+    let status = cmd.status()?;
+    check_status(cmd, status)
+}
+
 #[instrument(level = "trace", err, skip_all)]
 pub async fn download_file(url: &str, path: &Path) -> anyhow::Result<()> {
     fs::create_dir_all(&CACHE_DIR).await?;
@@ -68,10 +580,39 @@ pub async fn download_file(url: &str, path: &Path) -> anyhow::Result<()> {
             let content = response.bytes()
                 .await?
                 .to_vec();
-            fs::write(hex_url_path, &content).await?;
+            // Write then rename so concurrent builds never read a partial file
+            let temp_path = hex_url_path.with_extension(format!("{}.tmp", rand::random::<u64>()));
+            fs::write(&temp_path, &content).await?;
+            fs::rename(&temp_path, hex_url_path).await?;
             content
         };
 
+    write_if_changed(path, &content).await
+}
+
+/// Copy the file at `source` (a URL or local path) to `path`; URLs are first
+///  looked for in `mirror_dir`, laid out as by `wget -x`
+///  (e.g. `<mirror>/github.com/bytecodealliance/...`)
+#[instrument(level = "trace", err, skip_all)]
+async fn fetch_file(source: &str, path: &Path, mirror_dir: Option<&Path>) -> anyhow::Result<()> {
+    let local_path = match source.split_once("://") {
+        None => Some(PathBuf::from(source)),
+        Some((_scheme, mirrored_path)) => mirror_dir
+            .map(|mirror_dir| mirror_dir.join(mirrored_path))
+            .filter(|mirrored_path| mirrored_path.exists()),
+    };
+    match local_path {
+        None => download_file(source, path).await,
+        Some(local_path) => {
+            let content = fs::read(&local_path)
+                .await
+                .map_err(|e| anyhow::anyhow!("kit build: couldn't read {:?}: {}", local_path, e))?;
+            write_if_changed(path, &content).await
+        },
+    }
+}
+
+async fn write_if_changed(path: &Path, content: &[u8]) -> anyhow::Result<()> {
     if path.exists() {
         if path.is_dir() {
             fs::remove_dir_all(path).await?;
@@ -83,7 +624,7 @@ pub async fn download_file(url: &str, path: &Path) -> anyhow::Result<()> {
         }
     }
     fs::create_dir_all(path.parent().ok_or(anyhow::anyhow!("path doesn't have parent"))?).await?;
-    fs::write(path, &content).await?;
+    fs::write(path, content).await?;
     Ok(())
 }
 
@@ -91,11 +632,12 @@ pub async fn download_file(url: &str, path: &Path) -> anyhow::Result<()> {
 async fn compile_javascript_wasm_process(
     process_dir: &Path,
     valid_node: Option<String>,
-    verbose: bool,
+    sources: &BuildSources,
+    output: &BuildOutput,
 ) -> anyhow::Result<()> {
     info!("Compiling Javascript Kinode process in {:?}...", process_dir);
     let wit_dir = process_dir.join("wit");
-    download_file(KINODE_WIT_URL, &wit_dir.join("kinode.wit")).await?;
+    fetch_file(&sources.kinode_wit, &wit_dir.join("kinode.wit"), sources.mirror_dir.as_deref()).await?;
 
     let wasm_file_name = process_dir
         .file_name()
@@ -111,18 +653,14 @@ async fn compile_javascript_wasm_process(
         )})
         .unwrap_or_else(|| (install, componentize));
 
-    run_command(Command::new("bash")
+    output.run(Command::new("bash")
         .args(&["-c", &install])
         .current_dir(process_dir)
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
     )?;
 
-    run_command(Command::new("bash")
+    output.run(Command::new("bash")
         .args(&["-c", &componentize])
         .current_dir(process_dir)
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
     )?;
 
     info!("Done compiling Javascript Kinode process in {:?}.", process_dir);
@@ -130,35 +668,214 @@ async fn compile_javascript_wasm_process(
 }
 
 #[instrument(level = "trace", err, skip_all)]
-async fn compile_python_wasm_process(
+async fn compile_typescript_wasm_process(
     process_dir: &Path,
-    python: &str,
-    verbose: bool,
+    valid_node: Option<String>,
+    sources: &BuildSources,
+    output: &BuildOutput,
 ) -> anyhow::Result<()> {
-    info!("Compiling Python Kinode process in {:?}...", process_dir);
+    info!("Compiling Typescript Kinode process in {:?}...", process_dir);
     let wit_dir = process_dir.join("wit");
-    download_file(KINODE_WIT_URL, &wit_dir.join("kinode.wit")).await?;
+    fetch_file(&sources.kinode_wit, &wit_dir.join("kinode.wit"), sources.mirror_dir.as_deref()).await?;
 
     let wasm_file_name = process_dir
         .file_name()
         .and_then(|s| s.to_str())
         .unwrap();
 
-    run_command(Command::new(python)
-        .args(&["-m", "venv", PY_VENV_NAME])
-        .current_dir(process_dir)
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
+    let commands = [
+        "npm install".to_string(),
+        "npx tsc".to_string(),
+        format!("node componentize.mjs {wasm_file_name} {TYPESCRIPT_OUT_PATH}"),
+    ];
+    for command in commands {
+        let command = match valid_node {
+            Some(ref valid_node) => format!("source ~/.nvm/nvm.sh && nvm use {} && {}", valid_node, command),
+            None => command,
+        };
+        output.run(Command::new("bash")
+            .args(["-c", &command])
+            .current_dir(process_dir)
+        )?;
+    }
+
+    info!("Done compiling Typescript Kinode process in {:?}.", process_dir);
+    Ok(())
+}
+
+/// Build with a backend registered in kit config
+#[instrument(level = "trace", err, skip_all)]
+async fn compile_custom_wasm_process(
+    process_dir: &Path,
+    language: &str,
+    backend: &config::BuildBackend,
+    sources: &BuildSources,
+    output: &BuildOutput,
+) -> anyhow::Result<()> {
+    info!("Compiling {} Kinode process in {:?}...", language, process_dir);
+    let process_dir = process_dir.canonicalize()?;
+    let wit_dir = process_dir.join("wit");
+    fetch_file(&sources.kinode_wit, &wit_dir.join("kinode.wit"), sources.mirror_dir.as_deref()).await?;
+    let wasi_adapter = process_dir.join("wasi_snapshot_preview1.wasm");
+    fetch_file(&sources.wasi_adapter, &wasi_adapter, sources.mirror_dir.as_deref()).await?;
+
+    let wasm_file_name = process_dir
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap();
+    let wasm_path = process_dir
+        .parent()
+        .unwrap()
+        .join("pkg")
+        .join(format!("{}.wasm", wasm_file_name));
+    if wasm_path.exists() {
+        fs::remove_file(&wasm_path).await?;
+    }
+
+    output.run(Command::new("bash")
+        .args(["-c", &backend.command])
+        .current_dir(&process_dir)
+        .env("KIT_PROCESS_NAME", wasm_file_name)
+        .env("KIT_WIT_DIR", &wit_dir)
+        .env("KIT_WASI_ADAPTER", &wasi_adapter)
+        .env("KIT_WASM_PATH", &wasm_path)
     )?;
-    run_command(Command::new("bash")
-        .args(&[
-            "-c",
-            &format!("source ../{PY_VENV_NAME}/bin/activate && pip install {REQUIRED_PY_PACKAGE} && componentize-py -d ../wit/ -w process componentize lib -o ../../pkg/{wasm_file_name}.wasm"),
-        ])
-        .current_dir(process_dir.join("src"))
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
+    if !wasm_path.exists() {
+        return Err(anyhow::anyhow!(
+            "kit build: {} backend command `{}` did not write {:?}",
+            language,
+            backend.command,
+            wasm_path,
+        ));
+    }
+
+    info!("Done compiling {} Kinode process in {:?}.", language, process_dir);
+    Ok(())
+}
+
+/// `pip install` args to install from `wheelhouse`, if any, rather than PyPI
+fn get_pip_source_args(wheelhouse: Option<&Path>) -> Vec<String> {
+    match wheelhouse {
+        None => vec![],
+        Some(wheelhouse) => vec![
+            "--no-index".to_string(),
+            "--find-links".to_string(),
+            wheelhouse.to_string_lossy().to_string(),
+        ],
+    }
+}
+
+/// The shared venv, keyed by the versions of `python` & `componentize-py`,
+///  in which Python processes are built, creating it if need be
+#[instrument(level = "trace", err, skip_all)]
+fn get_python_venv(
+    python: &str,
+    wheelhouse: Option<&Path>,
+    output: &BuildOutput,
+) -> anyhow::Result<PathBuf> {
+    let python_version = get_command_output(Command::new(python).arg("--version"));
+    let key = hex::encode(Sha256::digest(format!("{}\n{}", python_version, REQUIRED_PY_PACKAGE)));
+    let venv_dir = PathBuf::from(CACHE_DIR)
+        .join(PY_VENVS_DIR)
+        .join(format!("{}-{}", REQUIRED_PY_PACKAGE.replace("==", "-"), &key[..16]));
+    if venv_dir.join(PY_VENV_READY_FILE).exists() {
+        return Ok(venv_dir);
+    }
+
+    // Another kit may be setting up the same venv: wait for it to finish
+    //  (the lock is released when `lock_file` is dropped)
+    std::fs::create_dir_all(venv_dir.parent().unwrap())?;
+    let lock_file = File::create(venv_dir.with_extension("lock"))?;
+    flock(lock_file.as_raw_fd(), FlockArg::LockExclusive)?;
+    if venv_dir.join(PY_VENV_READY_FILE).exists() {
+        return Ok(venv_dir);
+    }
+
+    info!("Creating Python venv with {} in {:?}...", REQUIRED_PY_PACKAGE, venv_dir);
+    // A venv without the ready file was not fully set up
+    if venv_dir.exists() {
+        std::fs::remove_dir_all(&venv_dir)?;
+    }
+    output.run(Command::new(python)
+        .args(["-m", "venv"])
+        .arg(&venv_dir)
     )?;
+    output.run(Command::new(venv_dir.join("bin").join("python"))
+        .args(["-m", "pip", "install"])
+        .args(get_pip_source_args(wheelhouse))
+        .arg(REQUIRED_PY_PACKAGE)
+    )?;
+    std::fs::write(venv_dir.join(PY_VENV_READY_FILE), "")?;
+    Ok(venv_dir)
+}
+
+/// Install the process' `src/requirements.txt`, if any, into its
+///  `PYTHON_DEPS_PATH` (unless already installed), returning that dir
+#[instrument(level = "trace", err, skip_all)]
+async fn install_python_requirements(
+    process_dir: &Path,
+    venv_dir: &Path,
+    wheelhouse: Option<&Path>,
+    output: &BuildOutput,
+) -> anyhow::Result<Option<PathBuf>> {
+    let requirements_path = process_dir.join(PYTHON_REQUIREMENTS_PATH);
+    if !requirements_path.exists() {
+        return Ok(None);
+    }
+    let deps_dir = process_dir.join(PYTHON_DEPS_PATH);
+    let hash_path = deps_dir.join(PYTHON_DEPS_HASH_FILE);
+    let requirements_hash = hex::encode(Sha256::digest(fs::read(&requirements_path).await?));
+    let installed_hash = fs::read_to_string(&hash_path).await.unwrap_or_default();
+    if installed_hash == requirements_hash {
+        return Ok(Some(deps_dir));
+    }
+
+    info!("Installing {:?}...", requirements_path);
+    if deps_dir.exists() {
+        fs::remove_dir_all(&deps_dir).await?;
+    }
+    output.run(Command::new(venv_dir.join("bin").join("python"))
+        .args(["-m", "pip", "install", "--target"])
+        .arg(&deps_dir)
+        .args(get_pip_source_args(wheelhouse))
+        .arg("-r")
+        .arg(&requirements_path)
+    )?;
+    fs::write(&hash_path, requirements_hash).await?;
+    Ok(Some(deps_dir))
+}
+
+#[instrument(level = "trace", err, skip_all)]
+async fn compile_python_wasm_process(
+    process_dir: &Path,
+    venv_dir: &Path,
+    sources: &BuildSources,
+    output: &BuildOutput,
+) -> anyhow::Result<()> {
+    info!("Compiling Python Kinode process in {:?}...", process_dir);
+    let wit_dir = process_dir.join("wit");
+    fetch_file(&sources.kinode_wit, &wit_dir.join("kinode.wit"), sources.mirror_dir.as_deref()).await?;
+
+    let wasm_file_name = process_dir
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap();
+
+    let deps_dir = install_python_requirements(
+        process_dir,
+        venv_dir,
+        sources.python_wheelhouse.as_deref(),
+        output,
+    ).await?;
+
+    let mut componentize = Command::new(venv_dir.join("bin").join("componentize-py"));
+    componentize
+        .args(["-d", "../wit/", "-w", "process", "componentize", "lib", "-p", "."])
+        .current_dir(process_dir.join("src"));
+    if let Some(deps_dir) = deps_dir {
+        componentize.arg("-p").arg(deps_dir);
+    }
+    output.run(componentize.args(["-o", &format!("../../pkg/{wasm_file_name}.wasm")]))?;
 
     info!("Done compiling Python Kinode process in {:?}.", process_dir);
     Ok(())
@@ -167,8 +884,10 @@ async fn compile_python_wasm_process(
 #[instrument(level = "trace", err, skip_all)]
 async fn compile_rust_wasm_process(
     process_dir: &Path,
-    verbose: bool,
-) -> anyhow::Result<()> {
+    sources: &BuildSources,
+    options: &BuildOptions,
+    output: &BuildOutput,
+) -> anyhow::Result<ProcessSizes> {
     info!("Compiling Rust Kinode process in {:?}...", process_dir);
 
     // Paths
@@ -182,26 +901,19 @@ async fn compile_rust_wasm_process(
     fs::create_dir_all(&bindings_dir).await?;
 
     // Check and download kinode.wit if wit_dir does not exist
-    download_file(KINODE_WIT_URL, &wit_dir.join("kinode.wit")).await?;
+    fetch_file(&sources.kinode_wit, &wit_dir.join("kinode.wit"), sources.mirror_dir.as_deref()).await?;
 
     // Check and download wasi_snapshot_preview1.wasm if it does not exist
     let wasi_snapshot_file = process_dir.join("wasi_snapshot_preview1.wasm");
-    let wasi_version = "15.0.1";  // TODO: un-hardcode
-    let wasi_snapshot_url = format!(
-        "https://github.com/bytecodealliance/wasmtime/releases/download/v{}/wasi_snapshot_preview1.reactor.wasm",
-        wasi_version,
-    );
-    download_file(&wasi_snapshot_url, &wasi_snapshot_file).await?;
+    fetch_file(&sources.wasi_adapter, &wasi_snapshot_file, sources.mirror_dir.as_deref()).await?;
 
     // Create target.wasm (compiled .wit) & world
-    run_command(Command::new("wasm-tools")
+    output.run(Command::new("wasm-tools")
         .args(&["component", "wit",
             wit_dir.to_str().unwrap(),
             "-o", &bindings_dir.join("target.wasm").to_str().unwrap(),
             "--wasm",
         ])
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
     )?;
 
     // Copy wit directory to bindings
@@ -215,16 +927,24 @@ async fn compile_rust_wasm_process(
     File::create(bindings_dir.join("world"))?;
 
     // Build the module using Cargo
-    run_command(Command::new("cargo")
-        .args(&["+nightly", "build",
-            "--release",
+    let mut cargo = Command::new("cargo");
+    cargo.args(["+nightly", "build"]);
+    if options.profile != Profile::Debug {
+        cargo.arg("--release");
+    }
+    if options.profile == Profile::Size {
+        cargo
+            .env("CARGO_PROFILE_RELEASE_OPT_LEVEL", "z")
+            .env("CARGO_PROFILE_RELEASE_LTO", "true")
+            .env("CARGO_PROFILE_RELEASE_CODEGEN_UNITS", "1");
+    }
+    output.run(cargo
+        .args([
             "--no-default-features",
             "--target", "wasm32-wasi",
             "--target-dir", "target",
         ])
         .current_dir(process_dir)
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
     )?;
 
     // Adapt the module using wasm-tools
@@ -235,30 +955,56 @@ async fn compile_rust_wasm_process(
         .and_then(|s| s.to_str())
         .unwrap();
 
-    let wasm_file_prefix = Path::new("target/wasm32-wasi/release");
+    let wasm_file_prefix = Path::new("target/wasm32-wasi")
+        .join(if options.profile == Profile::Debug { "debug" } else { "release" });
     let wasm_file = wasm_file_prefix
         .join(&format!("{}.wasm", wasm_file_name));
     let adapted_wasm_file = wasm_file_prefix
         .join(&format!("{}_adapted.wasm", wasm_file_name));
+    let module_size = get_file_size(&process_dir.join(&wasm_file));
+
+    // Post-process the module
+    let mut module_file = wasm_file.clone();
+    if options.is_strip() {
+        let stripped_wasm_file = wasm_file_prefix.join(format!("{}_stripped.wasm", wasm_file_name));
+        output.run(Command::new("wasm-tools")
+            .args(["strip",
+                module_file.to_str().unwrap(),
+                "-o", stripped_wasm_file.to_str().unwrap(),
+            ])
+            .current_dir(process_dir)
+        )?;
+        module_file = stripped_wasm_file;
+    }
+    if options.is_wasm_opt() {
+        let optimized_wasm_file = wasm_file_prefix.join(format!("{}_optimized.wasm", wasm_file_name));
+        output.run(Command::new("wasm-opt")
+            .args([
+                if options.profile == Profile::Size { "-Oz" } else { "-O" },
+                module_file.to_str().unwrap(),
+                "-o", optimized_wasm_file.to_str().unwrap(),
+            ])
+            .current_dir(process_dir)
+        )?;
+        module_file = optimized_wasm_file;
+    }
 
     let wasi_snapshot_file = Path::new("wasi_snapshot_preview1.wasm");
 
-    run_command(Command::new("wasm-tools")
+    output.run(Command::new("wasm-tools")
         .args(&["component", "new",
-            wasm_file.to_str().unwrap(),
+            module_file.to_str().unwrap(),
             "-o", adapted_wasm_file.to_str().unwrap(),
             "--adapt", wasi_snapshot_file.to_str().unwrap(),
         ])
         .current_dir(process_dir)
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
     )?;
 
     let wasm_path = format!("../pkg/{}.wasm", wasm_file_name);
     let wasm_path = Path::new(&wasm_path);
 
     // Embed wit into the component and place it in the expected location
-    run_command(Command::new("wasm-tools")
+    output.run(Command::new("wasm-tools")
         .args(&["component", "embed",
             wit_dir.strip_prefix(process_dir).unwrap().to_str().unwrap(),
             "--world", "process",
@@ -266,19 +1012,21 @@ async fn compile_rust_wasm_process(
             "-o", wasm_path.to_str().unwrap(),
         ])
         .current_dir(process_dir)
-        .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-        .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
     )?;
 
     info!("Done compiling Rust Kinode process in {:?}.", process_dir);
-    Ok(())
+    Ok(ProcessSizes {
+        module: module_size,
+        component: get_file_size(&process_dir.join(&adapted_wasm_file)),
+        packaged: None,
+    })
 }
 
 #[instrument(level = "trace", err, skip_all)]
 async fn compile_and_copy_ui(
     package_dir: &Path,
     valid_node: Option<String>,
-    verbose: bool,
+    output: &BuildOutput,
 ) -> anyhow::Result<()> {
     let ui_path = package_dir.join("ui");
     info!("Building UI in {:?}...", ui_path);
@@ -296,31 +1044,25 @@ async fn compile_and_copy_ui(
                 )})
                 .unwrap_or_else(|| (install, run));
 
-            run_command(Command::new("bash")
+            output.run(Command::new("bash")
                 .args(&["-c", &install])
                 .current_dir(&ui_path)
-                .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-                .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
             )?;
 
             info!("Running npm run build:copy...");
 
-            run_command(Command::new("bash")
+            output.run(Command::new("bash")
                 .args(&["-c", &run])
                 .current_dir(&ui_path)
-                .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-                .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
             )?;
         } else {
             let pkg_ui_path = package_dir.join("pkg/ui");
             if pkg_ui_path.exists() {
                 fs::remove_dir_all(&pkg_ui_path).await?;
             }
-            run_command(Command::new("cp")
+            output.run(Command::new("cp")
                 .args(["-r", "ui", "pkg/ui"])
                 .current_dir(&package_dir)
-                .stdout(if verbose { Stdio::inherit() } else { Stdio::null() })
-                .stderr(if verbose { Stdio::inherit() } else { Stdio::null() })
             )?;
         }
     } else {
@@ -331,48 +1073,437 @@ async fn compile_and_copy_ui(
     Ok(())
 }
 
+/// Number of processes to build in parallel when `--jobs` is not given
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Stdout of `cmd`, or empty if it can't be run
+fn get_command_output(cmd: &mut Command) -> String {
+    cmd.output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+/// A URL, or the hash of the contents of a local file (which may be under development)
+fn get_source_version(source: &str) -> String {
+    if is_url(source) {
+        return source.to_string();
+    }
+    std::fs::read(source)
+        .map(|contents| hex::encode(Sha256::digest(contents)))
+        .unwrap_or_default()
+}
+
+/// Versions of everything used to build a process other than its sources
+fn get_toolchain_version(process: &ProcessBuild) -> String {
+    let sources = &process.sources;
+    let mut toolchain = vec![
+        env!("CARGO_PKG_VERSION").to_string(),
+        get_source_version(&sources.kinode_wit),
+    ];
+    toolchain.extend(process.hooks.pre_build.iter().map(|hook| format!("pre_build: {}", hook)));
+    toolchain.extend(process.hooks.post_build.iter().map(|hook| format!("post_build: {}", hook)));
+    toolchain.push(format!("profile: {}", process.options.profile.as_str()));
+    match process.language {
+        ProcessLanguage::Rust => {
+            toolchain.push(get_source_version(&sources.wasi_adapter));
+            toolchain.push(get_command_output(Command::new("cargo").args(["+nightly", "--version"])));
+            toolchain.push(get_command_output(Command::new("wasm-tools").arg("--version")));
+            toolchain.push(format!("strip: {}", process.options.is_strip()));
+            if process.options.is_wasm_opt() {
+                toolchain.push(get_command_output(Command::new("wasm-opt").arg("--version")));
+            }
+        },
+        ProcessLanguage::Python => toolchain.push(REQUIRED_PY_PACKAGE.to_string()),
+        ProcessLanguage::Javascript | ProcessLanguage::Typescript => {
+            toolchain.push(get_command_output(Command::new("node").arg("--version")));
+        },
+        ProcessLanguage::Custom(ref name, ref backend) => {
+            toolchain.push(get_source_version(&sources.wasi_adapter));
+            toolchain.push(format!("{}: {}", name, backend.command));
+            if let Some(ref version_command) = backend.version_command {
+                toolchain.push(get_command_output(Command::new("bash").args(["-c", version_command])));
+            }
+        },
+    }
+    toolchain.join("\n")
+}
+
+/// Files & dirs outside the process dir that its build reads: Rust path
+///  dependencies (transitively) & lockfiles, and the Python wheelhouse
+fn get_external_sources(process: &ProcessBuild) -> Vec<PathBuf> {
+    let process_dir = &process.process_dir;
+    let mut sources = Vec::new();
+    match process.language {
+        ProcessLanguage::Rust => {
+            sources.push(process.package_dir.join("Cargo.lock"));
+            let mut to_visit = vec![process_dir.clone()];
+            let mut visited = HashSet::new();
+            while let Some(crate_dir) = to_visit.pop() {
+                if !visited.insert(crate_dir.clone()) {
+                    continue;
+                }
+                let metadata = get_command_output(Command::new("cargo")
+                    .args(["metadata", "--no-deps", "--offline", "--format-version", "1"])
+                    .current_dir(&crate_dir)
+                );
+                let Ok(metadata) = serde_json::from_str::<serde_json::Value>(&metadata) else {
+                    continue;
+                };
+                if !crate_dir.starts_with(process_dir) {
+                    sources.push(crate_dir.clone());
+                }
+                if let Some(workspace_root) = metadata["workspace_root"].as_str() {
+                    sources.push(Path::new(workspace_root).join("Cargo.lock"));
+                }
+                let dependencies = metadata["packages"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|package| package["dependencies"].as_array().into_iter().flatten());
+                for dependency in dependencies {
+                    if let Some(path) = dependency["path"].as_str() {
+                        to_visit.push(PathBuf::from(path));
+                    }
+                }
+            }
+        },
+        ProcessLanguage::Python => sources.extend(process.sources.python_wheelhouse.clone()),
+        _ => {},
+    }
+    sources.retain(|source| source.exists() && !source.starts_with(process_dir));
+    sources.sort();
+    sources.dedup();
+    sources
+}
+
+/// Hash the files under `path` (or the file at `path`), skipping build
+///  products, naming each by its path relative to `root`
+fn hash_files(hasher: &mut Sha256, root: &Path, path: &Path) -> anyhow::Result<()> {
+    let entries = WalkDir::new(path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() != 1
+                || !FINGERPRINT_IGNORED_DIRS.iter().any(|dir| entry.file_name() == *dir)
+        });
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative_path = entry.path().strip_prefix(root).unwrap_or(entry.path()).to_string_lossy().to_string();
+        let contents = std::fs::read(entry.path())?;
+        hasher.update((relative_path.len() as u64).to_le_bytes());
+        hasher.update(relative_path.as_bytes());
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(())
+}
+
+/// Hash of the sources (including `wit/`, path dependencies & lockfiles)
+///  of `process` and the toolchain used to build it
 #[instrument(level = "trace", err, skip_all)]
-async fn compile_package_and_ui(
-    package_dir: &Path,
-    valid_node: Option<String>,
-    verbose: bool,
-    skip_deps_check: bool,
+fn get_fingerprint(process: &ProcessBuild) -> anyhow::Result<String> {
+    let process_dir = &process.process_dir;
+    let mut hasher = Sha256::new();
+    hasher.update(process.toolchain.as_bytes());
+    hash_files(&mut hasher, process_dir, process_dir)?;
+    for source in get_external_sources(process) {
+        hash_files(&mut hasher, &process.package_dir, &source)?;
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// A process is current if its `pkg/<name>.wasm` exists and it was built
+///  from sources & toolchain matching `fingerprint`
+fn is_process_current(process: &ProcessBuild, fingerprint: &str) -> bool {
+    process.wasm_path().exists()
+        && std::fs::read_to_string(process.process_dir.join(FINGERPRINT_PATH))
+            .map(|stored| stored == fingerprint)
+            .unwrap_or(false)
+}
+
+/// Run `hooks` (the `hook` stage of `process`), failing on the first to fail
+fn run_hooks(
+    hook: &str,
+    hooks: &[String],
+    process: &ProcessBuild,
+    output: &BuildOutput,
 ) -> anyhow::Result<()> {
-    compile_and_copy_ui(package_dir, valid_node, verbose).await?;
-    compile_package(package_dir, verbose, skip_deps_check).await?;
+    for command in hooks {
+        info!("Running {} hook `{}` for {:?}...", hook, command, process.process_dir);
+        output.run(Command::new("bash")
+            .args(["-c", command])
+            .current_dir(&process.process_dir)
+            .env("KIT_PACKAGE_DIR", &process.package_dir)
+            .env("KIT_PROCESS_DIR", &process.process_dir)
+            .env("KIT_PROCESS_NAME", process.process_name())
+            .env("KIT_WASM_PATH", process.wasm_path())
+            .env("KIT_BUILD_MODE", process.options.profile.as_str())
+            .env("KIT_HOOK", hook)
+        ).map_err(|e| anyhow::anyhow!(
+            "kit build: {} hook `{}` failed for {:?}: {}",
+            hook,
+            command,
+            process.process_dir,
+            e,
+        ))?;
+    }
     Ok(())
 }
 
+/// Build `process`, running its hooks, and record its fingerprint
 #[instrument(level = "trace", err, skip_all)]
-async fn compile_package(
-    package_dir: &Path,
+async fn compile_process(
+    process: &ProcessBuild,
+    python_venv: Option<PathBuf>,
+    valid_node: Option<String>,
+    output: &BuildOutput,
+) -> anyhow::Result<ProcessSizes> {
+    let ProcessBuild { ref process_dir, ref language, ref sources, ref hooks, .. } = *process;
+    let process_dir = process_dir.as_path();
+    let fingerprint_path = process_dir.join(FINGERPRINT_PATH);
+    if fingerprint_path.exists() {
+        fs::remove_file(&fingerprint_path).await?;
+    }
+    run_hooks("pre_build", &hooks.pre_build, process, output)?;
+    let mut sizes = ProcessSizes::default();
+    match language {
+        ProcessLanguage::Rust => {
+            sizes = compile_rust_wasm_process(process_dir, sources, &process.options, output).await?
+        },
+        ProcessLanguage::Python => {
+            compile_python_wasm_process(process_dir, &python_venv.unwrap(), sources, output).await?
+        },
+        ProcessLanguage::Javascript => {
+            compile_javascript_wasm_process(process_dir, valid_node, sources, output).await?
+        },
+        ProcessLanguage::Typescript => {
+            compile_typescript_wasm_process(process_dir, valid_node, sources, output).await?
+        },
+        ProcessLanguage::Custom(name, backend) => {
+            compile_custom_wasm_process(process_dir, name, backend, sources, output).await?
+        },
+    }
+    run_hooks("post_build", &hooks.post_build, process, output)?;
+    // Fingerprint after building so that files the build & hooks write
+    //  into the process directory (e.g. `Cargo.lock`) are included
+    let fingerprint = get_fingerprint(process)?;
+    fs::create_dir_all(fingerprint_path.parent().unwrap()).await?;
+    fs::write(&fingerprint_path, fingerprint).await?;
+    sizes.packaged = get_file_size(&process.wasm_path());
+    Ok(sizes)
+}
+
+/// Build the processes of all `package_dirs` in parallel, reporting every failure
+#[instrument(level = "trace", err, skip_all)]
+async fn compile_packages(
+    package_dirs: &[PathBuf],
     verbose: bool,
     skip_deps_check: bool,
+    options: &BuildOptions,
 ) -> anyhow::Result<()> {
-    for entry in package_dir.read_dir()? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() {
-            if path.join(RUST_SRC_PATH).exists() {
-                if !skip_deps_check {
-                    let deps = check_rust_deps()?;
-                    get_deps(deps)?;
+    let mut processes = Vec::new();
+    let mut build_infos = Vec::new();
+    for package_dir in package_dirs {
+        let package_dir = &package_dir.canonicalize()?;
+        let config = config::load(package_dir)?;
+        let mut package_process_dirs = Vec::new();
+        for entry in package_dir.read_dir()? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            if let Some(language) = ProcessLanguage::detect(&path, &config.build.backends) {
+                package_process_dirs.push((path, language));
+            }
+        }
+        package_process_dirs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mirror_dir = std::env::var(MIRROR_DIR_ENV_VAR)
+            .ok()
+            .map(PathBuf::from)
+            .or(config.build.mirror.clone());
+        let python_wheelhouse = std::env::var(PYTHON_WHEELHOUSE_ENV_VAR)
+            .ok()
+            .map(PathBuf::from)
+            .or(config.build.python_wheelhouse.clone());
+        let sources = read_build_dependencies(package_dir)?
+            .or(config.build.dependencies())
+            .resolve(package_dir, mirror_dir, python_wheelhouse);
+        build_infos.push((package_dir.clone(), BuildInfo::new(&sources)));
+        for (process_dir, language) in package_process_dirs {
+            let process_name = process_dir.file_name().unwrap().to_string_lossy().to_string();
+            processes.push(ProcessBuild {
+                package_dir: package_dir.clone(),
+                process_dir,
+                language,
+                sources: sources.clone(),
+                hooks: config.build.get_hooks(&process_name),
+                options: *options,
+                toolchain: String::new(),
+            });
+        }
+    }
+
+    // Check dependencies once, up front, since doing so may prompt the user
+    let has_language = |is_language: fn(&ProcessLanguage) -> bool| {
+        processes.iter().any(|process| is_language(&process.language))
+    };
+    let has_rust = has_language(|l| matches!(l, ProcessLanguage::Rust));
+    if has_rust && !skip_deps_check {
+        let deps = check_rust_deps()?;
+        get_deps(deps)?;
+    }
+    if has_rust && options.is_wasm_opt() && !is_command_installed("wasm-opt")? {
+        return Err(anyhow::anyhow!(
+            "kit build: `wasm-opt` is required by --wasm-opt and the size profile; install binaryen or `cargo install wasm-opt`",
+        ));
+    }
+    let python = if has_language(|l| matches!(l, ProcessLanguage::Python)) {
+        Some(check_py_deps()?)
+    } else {
+        None
+    };
+    let valid_node = if has_language(ProcessLanguage::is_node) {
+        if !skip_deps_check {
+            let deps = check_js_deps()?;
+            get_deps(deps)?;
+        }
+        get_newest_valid_node_version(None, None)?
+    } else {
+        None
+    };
+
+    let is_many_packages = package_dirs.len() > 1;
+    let mut sizes = Vec::new();
+    let mut stale_processes = Vec::new();
+    for mut process in processes {
+        process.toolchain = get_toolchain_version(&process);
+        if let Some((_, build_info)) = build_infos.iter_mut().find(|(dir, _)| dir == &process.package_dir) {
+            build_info.add_process(&process);
+        }
+        if !options.force && is_process_current(&process, &get_fingerprint(&process)?) {
+            info!(
+                "{:?} is unchanged since it was last built; skipping (use --force to rebuild).",
+                process.process_dir,
+            );
+            let name = process.display_name(is_many_packages);
+            let process_sizes = ProcessSizes {
+                packaged: get_file_size(&process.wasm_path()),
+                ..Default::default()
+            };
+            options.message_format.emit(&BuildEvent {
+                skipped: true,
+                sizes: Some(process_sizes),
+                ..BuildEvent::new("finish", "process", &name)
+            });
+            sizes.push((name, process_sizes));
+        } else {
+            stale_processes.push(process);
+        }
+    }
+    let num_processes = stale_processes.len();
+    let is_concurrent = options.jobs > 1 && num_processes > 1;
+
+    // Set up the shared Python venv once, before the builds that use it
+    let stale_python = stale_processes
+        .iter()
+        .find(|process| matches!(process.language, ProcessLanguage::Python));
+    let python_venv = match (python, stale_python) {
+        (Some(python), Some(process)) => Some(get_python_venv(
+            &python,
+            process.sources.python_wheelhouse.as_deref(),
+            &BuildOutput::new(verbose, false, options.message_format, PY_VENVS_DIR),
+        )?),
+        _ => None,
+    };
+
+    // Builds mostly wait on blocking commands (`BuildOutput::run`), so each
+    //  runs on a blocking thread, driving its async parts with the runtime
+    let runtime = tokio::runtime::Handle::current();
+    let results: Vec<(PathBuf, String, anyhow::Result<ProcessSizes>)> = futures_util::stream::iter(stale_processes)
+        .map(|process| {
+            let python_venv = python_venv.clone();
+            let valid_node = valid_node.clone();
+            let message_format = options.message_format;
+            let runtime = runtime.clone();
+            let process_dir = process.process_dir.clone();
+            let display_name = process.display_name(is_many_packages);
+            let output = BuildOutput::new(verbose, is_concurrent, message_format, &display_name);
+            let handle = tokio::task::spawn_blocking(move || {
+                let name = process.display_name(is_many_packages);
+                message_format.emit(&BuildEvent::new("start", "process", &name));
+                let start = Instant::now();
+                let result = runtime.block_on(compile_process(&process, python_venv, valid_node, &output));
+                match result {
+                    Ok(ref process_sizes) => message_format.emit(&BuildEvent {
+                        duration_ms: Some(start.elapsed().as_millis()),
+                        sizes: Some(*process_sizes),
+                        ..BuildEvent::new("finish", "process", &name)
+                    }),
+                    Err(ref e) => message_format.emit(&BuildEvent {
+                        duration_ms: Some(start.elapsed().as_millis()),
+                        ..BuildEvent::error("process", &name, e, &output.captured())
+                    }),
                 }
-                compile_rust_wasm_process(&path, verbose).await?;
-            } else if path.join(PYTHON_SRC_PATH).exists() {
-                let python = check_py_deps()?;
-                compile_python_wasm_process(&path, &python, verbose).await?;
-            } else if path.join(JAVASCRIPT_SRC_PATH).exists() {
-                if !skip_deps_check {
-                    let deps = check_js_deps()?;
-                    get_deps(deps)?;
+                if let Err(e) = output.print() {
+                    warn!("Failed to print build output of {:?}: {}", process.process_dir, e);
                 }
-                let valid_node = get_newest_valid_node_version(None, None)?;
-                compile_javascript_wasm_process(&path, valid_node, verbose).await?;
+                (process.process_dir, name, result)
+            });
+            // Report a build that panics as a failure, like any other
+            async move {
+                handle.await.unwrap_or_else(|e| {
+                    let e = anyhow::anyhow!("kit build: process build panicked: {}", e);
+                    message_format.emit(&BuildEvent::error("process", &display_name, &e, ""));
+                    (process_dir, display_name, Err(e))
+                })
             }
+        })
+        .buffer_unordered(options.jobs.max(1))
+        .collect()
+        .await;
+
+    let mut failures = Vec::new();
+    for (process_dir, name, result) in results {
+        match result {
+            Ok(process_sizes) => sizes.push((name, process_sizes)),
+            Err(e) => failures.push(format!("{:?}: {:?}", process_dir, e)),
         }
     }
 
+    if !sizes.is_empty() {
+        sizes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        info!("Process sizes (module / component / packaged):");
+        for (name, process_sizes) in sizes {
+            info!(
+                "  {}: {} / {} / {}",
+                name,
+                format_size(process_sizes.module),
+                format_size(process_sizes.component),
+                format_size(process_sizes.packaged),
+            );
+        }
+    }
+    if !failures.is_empty() {
+        return Err(anyhow::anyhow!(
+            "kit build: {} of {} processes failed to build:\n{}",
+            failures.len(),
+            num_processes,
+            failures.join("\n"),
+        ));
+    }
+
+    for (package_dir, build_info) in build_infos {
+        build_info.write(&package_dir)?;
+    }
     Ok(())
 }
 
@@ -383,34 +1514,74 @@ pub async fn execute(
     ui_only: bool,
     verbose: bool,
     skip_deps_check: bool,
+    options: &BuildOptions,
 ) -> anyhow::Result<()> {
-    if !package_dir.join("pkg").exists() {
-        return Err(anyhow::anyhow!(
-            "Required `pkg/` dir not found within given input dir {:?} (or cwd, if none given). Please re-run targeting a package.",
-            package_dir,
-        ));
+    if !package_dir.join("ui").exists() && ui_only {
+        return Err(anyhow::anyhow!("kit build: can't build UI: no ui directory exists"));
     }
+    execute_packages(
+        &[package_dir.to_path_buf()],
+        no_ui,
+        ui_only,
+        verbose,
+        skip_deps_check,
+        options,
+    ).await
+}
 
-    let ui_dir = package_dir.join("ui");
-    if !ui_dir.exists() {
-        if ui_only {
-            return Err(anyhow::anyhow!("kit build: can't build UI: no ui directory exists"));
-        } else {
-            compile_package(package_dir, verbose, skip_deps_check).await
-        }
-    } else {
-        if no_ui {
-            return compile_package(package_dir, verbose, skip_deps_check).await;
+/// Build several packages: their UIs one at a time, then all their processes in parallel
+#[instrument(level = "trace", err, skip_all)]
+pub async fn execute_packages(
+    package_dirs: &[PathBuf],
+    no_ui: bool,
+    ui_only: bool,
+    verbose: bool,
+    skip_deps_check: bool,
+    options: &BuildOptions,
+) -> anyhow::Result<()> {
+    for package_dir in package_dirs {
+        if !package_dir.join("pkg").exists() {
+            return Err(anyhow::anyhow!(
+                "Required `pkg/` dir not found within given input dir {:?} (or cwd, if none given). Please re-run targeting a package.",
+                package_dir,
+            ));
         }
+    }
 
+    let ui_package_dirs: Vec<&PathBuf> = package_dirs
+        .iter()
+        .filter(|package_dir| package_dir.join("ui").exists())
+        .collect();
+    if !no_ui && !ui_package_dirs.is_empty() {
         let deps = check_js_deps()?;
         get_deps(deps)?;
         let valid_node = get_newest_valid_node_version(None, None)?;
-
-        if ui_only {
-            compile_and_copy_ui(package_dir, valid_node, verbose).await
-        } else {
-            compile_package_and_ui(package_dir, valid_node, verbose, skip_deps_check).await
+        for package_dir in ui_package_dirs {
+            let name = package_dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let output = BuildOutput::new(verbose, false, options.message_format, &name);
+            options.message_format.emit(&BuildEvent::new("start", "ui", &name));
+            let start = Instant::now();
+            let result = compile_and_copy_ui(package_dir, valid_node.clone(), &output).await;
+            options.message_format.emit(&match result {
+                Ok(()) => BuildEvent {
+                    duration_ms: Some(start.elapsed().as_millis()),
+                    ..BuildEvent::new("finish", "ui", &name)
+                },
+                Err(ref e) => BuildEvent {
+                    duration_ms: Some(start.elapsed().as_millis()),
+                    ..BuildEvent::error("ui", &name, e, &output.captured())
+                },
+            });
+            result?;
         }
     }
+
+    if ui_only {
+        return Ok(());
+    }
+    compile_packages(package_dirs, verbose, skip_deps_check, options).await?;
+    for package_dir in package_dirs {
+        check::validate(package_dir)?;
+    }
+    Ok(())
 }
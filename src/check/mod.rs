@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::{info, instrument, warn};
+
+const MANIFEST_PATH: &str = "pkg/manifest.json";
+const METADATA_PATH: &str = "pkg/metadata.json";
+
+/// `pkg/metadata.json`
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    pub package: String,
+    pub publisher: String,
+    pub version: [u32; 3],
+    /// Shown in the app store listing
+    pub description: Option<String>,
+    /// URL of the listing's icon
+    pub image: Option<String>,
+    pub website: Option<String>,
+}
+
+/// What the runtime does when a process exits
+#[derive(Debug, Deserialize)]
+pub enum OnExit {
+    None,
+    Restart,
+    /// Requests to send on exit
+    Requests(Vec<serde_json::Value>),
+}
+
+/// A process in `pkg/manifest.json`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestEntry {
+    pub process_name: String,
+    /// Relative to `pkg/`
+    pub process_wasm_path: String,
+    pub on_exit: OnExit,
+    pub request_networking: bool,
+    /// Each a process ID string or a `{"process": ..., "params": ...}` object
+    pub request_capabilities: Vec<serde_json::Value>,
+    /// Each a process ID string or a `{"process": ..., "params": ...}` object
+    pub grant_capabilities: Vec<serde_json::Value>,
+    pub public: bool,
+}
+
+/// Parse the file at `relative_path` in `package_dir`, naming the
+///  offending field on failure
+fn read_json<T: DeserializeOwned>(package_dir: &Path, relative_path: &str) -> anyhow::Result<T> {
+    let content = fs::read_to_string(package_dir.join(relative_path))
+        .map_err(|e| anyhow::anyhow!("{}: couldn't read: {}", relative_path, e))?;
+    let deserializer = &mut serde_json::Deserializer::from_str(&content);
+    serde_path_to_error::deserialize(deserializer)
+        .map_err(|e| anyhow::anyhow!("{}: {}: {}", relative_path, e.path(), e.inner()))
+}
+
+/// A name that can be one part of a process ID
+fn is_id_part(name: &str) -> bool {
+    !name.is_empty() && !name.contains(':') && !name.chars().any(char::is_whitespace)
+}
+
+/// `process:package:publisher`
+fn is_process_id(id: &str) -> bool {
+    let parts: Vec<&str> = id.split(':').collect();
+    parts.len() == 3 && parts.iter().all(|part| is_id_part(part))
+}
+
+fn check_capability(capability: &serde_json::Value) -> Result<(), String> {
+    let process = match capability {
+        serde_json::Value::String(process) => process,
+        serde_json::Value::Object(capability) => {
+            let Some(serde_json::Value::String(process)) = capability.get("process") else {
+                return Err("expected a `process` string".into());
+            };
+            if !capability.contains_key("params") {
+                return Err("expected a `params` field".into());
+            }
+            process
+        },
+        _ => return Err(
+            "expected a process ID string or a `{\"process\": ..., \"params\": ...}` object".into()
+        ),
+    };
+    if !is_process_id(process) {
+        return Err(format!("{:?} is not a process ID of the form `process:package:publisher`", process));
+    }
+    Ok(())
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub fn read_metadata(package_dir: &Path) -> anyhow::Result<Metadata> {
+    let metadata: Metadata = read_json(package_dir, METADATA_PATH)?;
+    for (field, value) in [("package", &metadata.package), ("publisher", &metadata.publisher)] {
+        if !is_id_part(value) {
+            return Err(anyhow::anyhow!(
+                "{}: {}: {:?} must be non-empty with no `:` or whitespace",
+                METADATA_PATH,
+                field,
+                value,
+            ));
+        }
+    }
+    for (field, value) in [("image", &metadata.image), ("website", &metadata.website)] {
+        if let Some(value) = value {
+            let is_url = ["http://", "https://", "data:"].iter().any(|scheme| value.starts_with(scheme));
+            if !is_url {
+                return Err(anyhow::anyhow!(
+                    "{}: {}: {:?} must be an `http://`, `https://` or `data:` URL",
+                    METADATA_PATH,
+                    field,
+                    value,
+                ));
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+/// Problems with `manifest`, each prefixed with the offending field
+fn check_manifest(package_dir: &Path, manifest: &[ManifestEntry]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut process_names = HashSet::new();
+    for (i, entry) in manifest.iter().enumerate() {
+        let field = |name: &str| format!("{}: [{}].{}", MANIFEST_PATH, i, name);
+        if !is_id_part(&entry.process_name) {
+            problems.push(format!(
+                "{}: {:?} must be non-empty with no `:` or whitespace",
+                field("process_name"),
+                entry.process_name,
+            ));
+        } else if !process_names.insert(entry.process_name.as_str()) {
+            problems.push(format!(
+                "{}: {:?} is used by more than one process",
+                field("process_name"),
+                entry.process_name,
+            ));
+        }
+
+        let wasm_path = entry.process_wasm_path.trim_start_matches('/');
+        if !wasm_path.ends_with(".wasm") {
+            problems.push(format!(
+                "{}: {:?} is not a `.wasm` file",
+                field("process_wasm_path"),
+                entry.process_wasm_path,
+            ));
+        } else if !package_dir.join("pkg").join(wasm_path).is_file() {
+            let built = get_wasm_file_names(package_dir);
+            problems.push(format!(
+                "{}: {:?} not found in pkg/ ({})",
+                field("process_wasm_path"),
+                entry.process_wasm_path,
+                if built.is_empty() {
+                    "no processes are built; run `kit build`".to_string()
+                } else {
+                    format!("built: {}", built.join(", "))
+                },
+            ));
+        }
+
+        if let OnExit::Requests(requests) = &entry.on_exit {
+            for (j, request) in requests.iter().enumerate() {
+                if !matches!(request.as_array(), Some(request) if request.len() == 3) {
+                    problems.push(format!(
+                        "{}.Requests[{}]: expected an `[address, request, blob]` array",
+                        field("on_exit"),
+                        j,
+                    ));
+                }
+            }
+        }
+
+        for (name, capabilities) in [
+            ("request_capabilities", &entry.request_capabilities),
+            ("grant_capabilities", &entry.grant_capabilities),
+        ] {
+            for (j, capability) in capabilities.iter().enumerate() {
+                if let Err(e) = check_capability(capability) {
+                    problems.push(format!("{}[{}]: {}", field(name), j, e));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Names of the `.wasm` files at the top level of `pkg/`
+fn get_wasm_file_names(package_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(package_dir.join("pkg"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".wasm"))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Check `pkg/metadata.json` & `pkg/manifest.json`, including that the
+///  manifest's processes are built
+#[instrument(level = "trace", err, skip_all)]
+pub fn validate(package_dir: &Path) -> anyhow::Result<Metadata> {
+    let (metadata, _) = read_package(package_dir)?;
+    Ok(metadata)
+}
+
+fn read_package(package_dir: &Path) -> anyhow::Result<(Metadata, Vec<ManifestEntry>)> {
+    if !package_dir.join("pkg").exists() {
+        return Err(anyhow::anyhow!(
+            "Required `pkg/` dir not found within given input dir {:?} (or cwd, if none given). Please re-run targeting a package.",
+            package_dir,
+        ));
+    }
+    let metadata = read_metadata(package_dir)?;
+    let manifest: Vec<ManifestEntry> = read_json(package_dir, MANIFEST_PATH)?;
+
+    let problems = check_manifest(package_dir, &manifest);
+    if !problems.is_empty() {
+        return Err(anyhow::anyhow!(
+            "package {:?} is invalid:\n{}",
+            package_dir,
+            problems.join("\n"),
+        ));
+    }
+
+    for name in get_wasm_file_names(package_dir) {
+        if !manifest.iter().any(|e| e.process_wasm_path.trim_start_matches('/') == name) {
+            warn!("pkg/{} is not used by any process in {}.", name, MANIFEST_PATH);
+        }
+    }
+    Ok((metadata, manifest))
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub fn execute(package_dir: &Path) -> anyhow::Result<()> {
+    let (metadata, manifest) = read_package(package_dir)?;
+    info!(
+        "{}:{} v{}.{}.{} in {:?} is valid:",
+        metadata.package,
+        metadata.publisher,
+        metadata.version[0],
+        metadata.version[1],
+        metadata.version[2],
+        package_dir,
+    );
+    for entry in manifest {
+        let on_exit = match entry.on_exit {
+            OnExit::None => "none".to_string(),
+            OnExit::Restart => "restart".to_string(),
+            OnExit::Requests(requests) => format!("send {} requests", requests.len()),
+        };
+        info!(
+            "  {} ({}): on exit {}; {} requested & {} granted capabilities{}{}",
+            entry.process_name,
+            entry.process_wasm_path,
+            on_exit,
+            entry.request_capabilities.len(),
+            entry.grant_capabilities.len(),
+            if entry.request_networking { "; networking" } else { "" },
+            if entry.public { "; public" } else { "" },
+        );
+    }
+    Ok(())
+}
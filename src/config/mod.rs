@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use dirs::home_dir;
+use serde::Deserialize;
+use tracing::{debug, instrument};
+
+use super::build::BuildDependencies;
+
+pub const PROJECT_CONFIG_FILE_NAME: &str = "kit.toml";
+const USER_CONFIG_PATH: &str = ".config/kit/config.toml";
+/// Keys whose values are paths: relative paths are relative to the file they are set in
+const PATH_KEYS: [(&str, &str); 7] = [
+    ("build", "mirror"),
+    ("build", "python_wheelhouse"),
+    ("build", "wit"),
+    ("build", "wasi_adapter"),
+    ("fake_node", "home"),
+    ("fake_node", "runtime_path"),
+    ("tests", "config"),
+];
+
+/// Defaults for kit, from `kit.toml` (found by walking up from the package
+///  dir or cwd) overriding `~/.config/kit/config.toml`. Both are overridden by
+///  environment variables, which are in turn overridden by CLI flags
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KitConfig {
+    pub node: NodeConfig,
+    pub fake_node: FakeNodeConfig,
+    pub build: BuildConfig,
+    pub new: NewConfig,
+    pub tests: TestsConfig,
+    pub workspace: WorkspaceConfig,
+}
+
+/// The node that packages are started on, messages injected into, etc.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    /// Overrides `port`
+    pub url: Option<String>,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FakeNodeConfig {
+    pub home: Option<PathBuf>,
+    pub name: Option<String>,
+    pub password: Option<String>,
+    pub network_router_port: Option<u16>,
+    pub version: Option<String>,
+    pub runtime_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildConfig {
+    pub no_ui: bool,
+    pub skip_deps_check: bool,
+    pub quiet: bool,
+    pub jobs: Option<usize>,
+    /// `debug`, `release` or `size`
+    pub profile: Option<String>,
+    pub wasm_opt: bool,
+    pub strip: bool,
+    /// Overridden by `KIT_MIRROR_DIR`
+    pub mirror: Option<PathBuf>,
+    /// Directory of wheels (e.g. from `pip download`) to install
+    ///  `componentize-py` & Python process requirements from, offline;
+    ///  overridden by `KIT_PYTHON_WHEELHOUSE`
+    pub python_wheelhouse: Option<PathBuf>,
+    pub wit_version: Option<String>,
+    pub wit: Option<String>,
+    pub wasi_version: Option<String>,
+    pub wasi_adapter: Option<String>,
+    /// Language name -> how to build processes in that language
+    pub backends: BTreeMap<String, BuildBackend>,
+    /// Run when building any process
+    pub hooks: BuildHooks,
+    /// Process name -> hooks run after `hooks` when building that process
+    pub process_hooks: BTreeMap<String, BuildHooks>,
+}
+
+/// Commands run with `bash -c` in the process dir before & after it is
+///  built, with `KIT_PACKAGE_DIR`, `KIT_PROCESS_DIR`, `KIT_PROCESS_NAME`,
+///  `KIT_WASM_PATH`, `KIT_BUILD_MODE` and `KIT_HOOK` (`pre_build` or
+///  `post_build`) set; a failing hook fails the build
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildHooks {
+    pub pre_build: Vec<String>,
+    pub post_build: Vec<String>,
+}
+
+/// A `kit build` backend for a language kit doesn't build itself, e.g.
+///  `[build.backends.go]`. `command` is run with `bash -c` in the process
+///  dir with `KIT_PROCESS_NAME`, `KIT_WIT_DIR`, `KIT_WASI_ADAPTER` and
+///  `KIT_WASM_PATH` set, and must write the process component to `KIT_WASM_PATH`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildBackend {
+    /// Relative to the process dir: processes containing it use this backend
+    pub src: String,
+    pub command: String,
+    /// Prints the toolchain version, so processes rebuild when it changes
+    #[serde(default)]
+    pub version_command: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NewConfig {
+    pub publisher: Option<String>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TestsConfig {
+    pub config: Option<PathBuf>,
+}
+
+/// Set in the `kit.toml` at the root of a workspace
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkspaceConfig {
+    /// Package dirs, relative to the `kit.toml`
+    pub members: Vec<PathBuf>,
+    /// Member name (package dir name) -> names of members it depends on
+    pub dependencies: HashMap<String, Vec<String>>,
+}
+
+impl BuildConfig {
+    /// Hooks for every process followed by those for `process_name`
+    pub fn get_hooks(&self, process_name: &str) -> BuildHooks {
+        let mut hooks = self.hooks.clone();
+        if let Some(process_hooks) = self.process_hooks.get(process_name) {
+            hooks.pre_build.extend(process_hooks.pre_build.iter().cloned());
+            hooks.post_build.extend(process_hooks.post_build.iter().cloned());
+        }
+        hooks
+    }
+
+    pub fn dependencies(&self) -> BuildDependencies {
+        BuildDependencies {
+            wit_version: self.wit_version.clone(),
+            wit: self.wit.clone(),
+            wasi_version: self.wasi_version.clone(),
+            wasi_adapter: self.wasi_adapter.clone(),
+        }
+    }
+}
+
+fn find_project_config(start_dir: &Path) -> Option<PathBuf> {
+    let start_dir = fs::canonicalize(start_dir)
+        .or_else(|_| std::env::current_dir())
+        .ok()?;
+    start_dir
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+#[instrument(level = "trace", err, skip_all)]
+fn read_config_table(path: &Path) -> anyhow::Result<toml::Table> {
+    let mut table: toml::Table = toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| anyhow::anyhow!("kit: couldn't parse config {:?}: {}", path, e))?;
+    let config_dir = path.parent().unwrap_or(Path::new("."));
+    for (section, key) in PATH_KEYS {
+        let Some(toml::Value::Table(section)) = table.get_mut(section) else {
+            continue;
+        };
+        if let Some(toml::Value::String(value)) = section.get_mut(key) {
+            if !value.contains("://") && Path::new(value.as_str()).is_relative() {
+                *value = config_dir.join(&value).to_string_lossy().to_string();
+            }
+        }
+    }
+    Ok(table)
+}
+
+/// Recursively merge `overlay` into `base`, with `overlay` taking precedence
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            },
+        }
+    }
+}
+
+/// Load the user config, then the first `kit.toml` at or above `start_dir`
+#[instrument(level = "trace", err, skip_all)]
+pub fn load(start_dir: &Path) -> anyhow::Result<KitConfig> {
+    let mut table = toml::Table::new();
+    let user_config_path = home_dir().map(|home| home.join(USER_CONFIG_PATH));
+    let project_config_path = find_project_config(start_dir);
+    for path in [user_config_path, project_config_path].into_iter().flatten() {
+        if path.is_file() {
+            debug!("Using config {:?}", path);
+            merge(&mut table, read_config_table(&path)?);
+        }
+    }
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e| anyhow::anyhow!("kit: invalid config: {}", e))
+}
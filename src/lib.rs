@@ -1,11 +1,21 @@
 pub mod boot_fake_node;
 pub mod build;
 pub mod build_start_package;
+pub mod check;
+pub mod config;
 pub mod dev_ui;
 pub mod inject_message;
 pub mod new;
+pub mod nodes;
+pub mod package;
+pub mod publish;
 pub mod remove_package;
+pub mod replay;
 pub mod run_tests;
 pub mod setup;
+pub mod sniff;
+pub mod snapshot;
 pub mod start_package;
 pub mod update;
+pub mod watch;
+pub mod workspace;
@@ -1,23 +1,36 @@
-use clap::{Arg, ArgAction, builder::PossibleValuesParser, command, Command, value_parser};
+use clap::{Arg, ArgAction, builder::PossibleValuesParser, command, Command, parser::ValueSource, value_parser};
 use std::env;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use serde::Deserialize;
 use tracing::{warn, error, instrument, Level};
 use tracing_subscriber::{prelude::*, filter, fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+use config::KitConfig;
+
 mod boot_fake_node;
 mod build;
 mod build_start_package;
+mod check;
+mod config;
 mod dev_ui;
 mod inject_message;
 mod new;
+mod nodes;
+mod package;
+mod publish;
 mod remove_package;
+mod replay;
 mod run_tests;
 mod setup;
+mod sniff;
+mod snapshot;
 mod start_package;
 mod update;
+mod watch;
+mod workspace;
 
 const MAX_REMOTE_VALUES: usize = 3;
 const GIT_COMMIT_HASH: &str = env!("GIT_COMMIT_SHA");
@@ -48,6 +61,18 @@ async fn get_latest_commit_sha_from_branch(
     Ok(serde_json::from_slice(&bytes)?)
 }
 
+/// Set once `--message-format json` is parsed, reserving stdout for build events
+static IS_STDOUT_LOG_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Where logs meant for stdout go: stderr if `IS_STDOUT_LOG_TO_STDERR`
+fn make_stdout_log_writer() -> Box<dyn std::io::Write> {
+    if IS_STDOUT_LOG_TO_STDERR.load(Ordering::Relaxed) {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    }
+}
+
 #[instrument(level = "trace", err, skip_all)]
 fn init_tracing(log_path: PathBuf) -> anyhow::Result<tracing_appender::non_blocking::WorkerGuard> {
     // Define a fixed log file name with rolling based on size or execution instance.
@@ -81,7 +106,7 @@ fn init_tracing(log_path: PathBuf) -> anyhow::Result<tracing_appender::non_block
         .with(
             fmt::layer()
                 .without_time()
-                .with_writer(std::io::stdout)
+                .with_writer(make_stdout_log_writer)
                 .with_ansi(true)
                 .with_level(false)
                 .with_target(false)
@@ -112,15 +137,57 @@ fn init_tracing(log_path: PathBuf) -> anyhow::Result<tracing_appender::non_block
     Ok(guard)
 }
 
+/// `--url` if given, else `--port` on localhost if given, else the
+///  configured node URL, else the (configured) default port on localhost
+fn get_node_url(matches: &clap::ArgMatches, config: &KitConfig) -> String {
+    if let Some(url) = matches.get_one::<String>("URL") {
+        return url.clone();
+    }
+    let is_port_given = matches.value_source("NODE_PORT") != Some(ValueSource::DefaultValue);
+    match config.node.url {
+        Some(ref url) if !is_port_given => url.clone(),
+        _ => format!("http://localhost:{}", matches.get_one::<u16>("NODE_PORT").unwrap()),
+    }
+}
+
+fn get_member_names(matches: &clap::ArgMatches) -> Vec<String> {
+    matches.get_many::<String>("MEMBER")
+        .unwrap_or_default()
+        .cloned()
+        .collect()
+}
+
+/// `kit build` options from `matches`, falling back to kit config
+fn get_build_options(matches: &clap::ArgMatches, config: &KitConfig) -> anyhow::Result<build::BuildOptions> {
+    Ok(build::BuildOptions {
+        force: matches.try_get_one::<bool>("FORCE").ok().flatten().copied().unwrap_or(false),
+        jobs: matches.get_one::<usize>("JOBS")
+            .copied()
+            .or(config.build.jobs)
+            .unwrap_or_else(build::default_jobs),
+        profile: matches.get_one::<String>("PROFILE").unwrap().parse()?,
+        wasm_opt: *matches.get_one::<bool>("WASM_OPT").unwrap(),
+        strip: *matches.get_one::<bool>("STRIP").unwrap(),
+        message_format: matches.try_get_one::<String>("MESSAGE_FORMAT")
+            .ok()
+            .flatten()
+            .map(|f| f.parse())
+            .transpose()?
+            .unwrap_or_default(),
+    })
+}
+
 async fn execute(
     usage: clap::builder::StyledStr,
     matches: Option<(&str, &clap::ArgMatches)>,
+    config: &KitConfig,
 ) -> anyhow::Result<()> {
     match matches {
         Some(("boot-fake-node", boot_matches)) => {
             let runtime_path = boot_matches
                 .get_one::<String>("PATH")
-                .and_then(|p| Some(PathBuf::from(p)));
+                .map(PathBuf::from)
+                .or_else(|| config.fake_node.runtime_path.clone());
             let version = boot_matches.get_one::<String>("VERSION").unwrap();
             let node_home = PathBuf::from(boot_matches.get_one::<String>("HOME").unwrap());
             let node_port = boot_matches.get_one::<u16>("NODE_PORT").unwrap();
@@ -131,6 +198,11 @@ async fn execute(
             let password = boot_matches.get_one::<String>("PASSWORD").unwrap();
             let is_persist = boot_matches.get_one::<bool>("PERSIST").unwrap();
             let release = boot_matches.get_one::<bool>("RELEASE").unwrap();
+            let record = boot_matches.get_one::<String>("RECORD").map(PathBuf::from);
+            let topology = boot_matches.get_one::<String>("TOPOLOGY").map(PathBuf::from);
+            let count = boot_matches.get_one::<u16>("COUNT").unwrap();
+            let detach = boot_matches.get_one::<bool>("DETACH").unwrap();
+            let background = boot_matches.get_one::<bool>("BACKGROUND").unwrap();
 
             boot_fake_node::execute(
                 runtime_path,
@@ -144,6 +216,11 @@ async fn execute(
                 password,
                 *is_persist,
                 *release,
+                record,
+                topology,
+                *count,
+                *detach,
+                *background,
                 vec![],
             ).await
         },
@@ -153,8 +230,26 @@ async fn execute(
             let ui_only = build_matches.get_one::<bool>("UI_ONLY").unwrap();
             let verbose = !build_matches.get_one::<bool>("QUIET").unwrap();
             let skip_deps_check = build_matches.get_one::<bool>("SKIP_DEPS_CHECK").unwrap();
+            let options = get_build_options(build_matches, config)?;
 
-            build::execute(&package_dir, *no_ui, *ui_only, verbose, *skip_deps_check).await
+            match workspace::find_members(&package_dir, &get_member_names(build_matches))? {
+                Some(members) => workspace::build(
+                    &members,
+                    *no_ui,
+                    *ui_only,
+                    verbose,
+                    *skip_deps_check,
+                    &options,
+                ).await,
+                None => build::execute(
+                    &package_dir,
+                    *no_ui,
+                    *ui_only,
+                    verbose,
+                    *skip_deps_check,
+                    &options,
+                ).await,
+            }
         },
         Some(("build-start-package", build_start_matches)) => {
 
@@ -162,45 +257,46 @@ async fn execute(
             let no_ui = build_start_matches.get_one::<bool>("NO_UI").unwrap();
             let ui_only = build_start_matches.get_one::<bool>("UI_ONLY").unwrap_or(&false);
             let verbose = !build_start_matches.get_one::<bool>("QUIET").unwrap();
-            let url: String = match build_start_matches.get_one::<String>("URL") {
-                Some(url) => url.clone(),
-                None => {
-                    let port = build_start_matches.get_one::<u16>("NODE_PORT").unwrap();
-                    format!("http://localhost:{}", port)
-                },
-            };
+            let url = get_node_url(build_start_matches, config);
             let skip_deps_check = build_start_matches.get_one::<bool>("SKIP_DEPS_CHECK").unwrap();
+            let options = get_build_options(build_start_matches, config)?;
 
-            build_start_package::execute(
-                &package_dir,
-                *no_ui,
-                *ui_only,
-                verbose,
-                &url,
-                *skip_deps_check,
-            ).await
+            match workspace::find_members(&package_dir, &get_member_names(build_start_matches))? {
+                Some(members) => {
+                    workspace::build(
+                        &members,
+                        *no_ui,
+                        *ui_only,
+                        verbose,
+                        *skip_deps_check,
+                        &options,
+                    ).await?;
+                    workspace::start(&members, &url).await
+                },
+                None => build_start_package::execute(
+                    &package_dir,
+                    *no_ui,
+                    *ui_only,
+                    verbose,
+                    &url,
+                    *skip_deps_check,
+                    &options,
+                ).await,
+            }
+        },
+        Some(("check", check_matches)) => {
+            let package_dir = PathBuf::from(check_matches.get_one::<String>("DIR").unwrap());
+            check::execute(&package_dir)
         },
         Some(("dev-ui", dev_ui_matches)) => {
             let package_dir = PathBuf::from(dev_ui_matches.get_one::<String>("DIR").unwrap());
-            let url: String = match dev_ui_matches.get_one::<String>("URL") {
-                Some(url) => url.clone(),
-                None => {
-                    let port = dev_ui_matches.get_one::<u16>("NODE_PORT").unwrap();
-                    format!("http://localhost:{}", port)
-                },
-            };
+            let url = get_node_url(dev_ui_matches, config);
             let skip_deps_check = dev_ui_matches.get_one::<bool>("SKIP_DEPS_CHECK").unwrap();
 
             dev_ui::execute(&package_dir, &url, *skip_deps_check)
         },
         Some(("inject-message", inject_message_matches)) => {
-            let url: String = match inject_message_matches.get_one::<String>("URL") {
-                Some(url) => url.clone(),
-                None => {
-                    let port = inject_message_matches.get_one::<u16>("NODE_PORT").unwrap();
-                    format!("http://localhost:{}", port)
-                },
-            };
+            let url = get_node_url(inject_message_matches, config);
             let process: &String = inject_message_matches.get_one("PROCESS").unwrap();
             let non_block: &bool = inject_message_matches.get_one("NONBLOCK").unwrap();
             let body: &String = inject_message_matches.get_one("BODY_JSON").unwrap();
@@ -237,6 +333,44 @@ async fn execute(
                 *ui,
             )
         },
+        Some(("nodes", nodes_matches)) => match nodes_matches.subcommand() {
+            Some(("list", _list_matches)) => nodes::list(),
+            Some(("stop", stop_matches)) => {
+                let name = stop_matches.get_one::<String>("NODE_NAME").unwrap();
+                nodes::stop(name).await
+            },
+            Some(("logs", logs_matches)) => {
+                let name = logs_matches.get_one::<String>("NODE_NAME").unwrap();
+                let follow = logs_matches.get_one::<bool>("FOLLOW").unwrap();
+                nodes::logs(name, *follow).await
+            },
+            _ => unreachable!(),
+        },
+        Some(("package", package_matches)) => {
+            let package_dir = PathBuf::from(package_matches.get_one::<String>("DIR").unwrap());
+            let output = package_matches.get_one::<String>("OUTPUT").map(PathBuf::from);
+            let no_ui = package_matches.get_one::<bool>("NO_UI").unwrap();
+            let verbose = !package_matches.get_one::<bool>("QUIET").unwrap();
+            let skip_deps_check = package_matches.get_one::<bool>("SKIP_DEPS_CHECK").unwrap();
+            let options = get_build_options(package_matches, config)?;
+
+            package::execute(
+                &package_dir,
+                output.as_deref(),
+                *no_ui,
+                verbose,
+                *skip_deps_check,
+                &options,
+            ).await
+        },
+        Some(("publish", publish_matches)) => {
+            let package_dir = PathBuf::from(publish_matches.get_one::<String>("DIR").unwrap());
+            let url = get_node_url(publish_matches, config);
+            let no_mirror = publish_matches.get_one::<bool>("NO_MIRROR").unwrap();
+            let install = publish_matches.get_one::<bool>("INSTALL").unwrap();
+
+            publish::execute(&package_dir, &url, !no_mirror, *install).await
+        },
         Some(("run-tests", run_tests_matches)) => {
             let config_path = match run_tests_matches.get_one::<String>("PATH") {
                 Some(path) => PathBuf::from(path),
@@ -252,7 +386,23 @@ async fn execute(
                 return Err(anyhow::anyhow!(error));
             }
 
-            run_tests::execute(config_path.to_str().unwrap()).await
+            let reports = run_tests_matches.get_many::<run_tests::report::Report>("REPORT")
+                .unwrap_or_default()
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let keep_going = run_tests_matches.get_one::<bool>("KEEP_GOING").unwrap();
+            let filter = run_tests_matches.get_one::<regex::Regex>("FILTER").cloned();
+
+            let jobs = run_tests_matches.get_one::<usize>("JOBS").unwrap();
+
+            run_tests::execute(
+                config_path.to_str().unwrap(),
+                reports,
+                *keep_going,
+                filter,
+                *jobs,
+            ).await
         },
         Some(("remove-package", remove_package_matches)) => {
             let package_name = remove_package_matches.get_one::<String>("PACKAGE")
@@ -260,26 +410,71 @@ async fn execute(
             let publisher = remove_package_matches.get_one::<String>("PUBLISHER")
                 .and_then(|s: &String| Some(s.as_str()));
             let package_dir = PathBuf::from(remove_package_matches.get_one::<String>("DIR").unwrap());
-            let url: String = match remove_package_matches.get_one::<String>("URL") {
-                Some(url) => url.clone(),
-                None => {
-                    let port = remove_package_matches.get_one::<u16>("NODE_PORT").unwrap();
-                    format!("http://localhost:{}", port)
-                },
-            };
+            let url = get_node_url(remove_package_matches, config);
             remove_package::execute(&package_dir, &url, package_name, publisher).await
         },
+        Some(("replay", replay_matches)) => {
+            let recording_path = PathBuf::from(replay_matches.get_one::<String>("RECORDING").unwrap());
+            let runtime_path = replay_matches
+                .get_one::<String>("PATH")
+                .map(PathBuf::from)
+                .or_else(|| config.fake_node.runtime_path.clone());
+            let version = replay_matches.get_one::<String>("VERSION").unwrap();
+            let release = replay_matches.get_one::<bool>("RELEASE").unwrap();
+            let home_prefix = PathBuf::from(replay_matches.get_one::<String>("HOME").unwrap());
+            let node_port = replay_matches.get_one::<u16>("NODE_PORT").unwrap();
+            let network_router_port = replay_matches.get_one::<u16>("NETWORK_ROUTER_PORT").unwrap();
+            let speed = replay_matches.get_one::<f64>("SPEED").unwrap();
+            let include_dropped = replay_matches.get_one::<bool>("INCLUDE_DROPPED").unwrap();
+
+            replay::execute(
+                &recording_path,
+                runtime_path,
+                version.clone(),
+                *release,
+                home_prefix,
+                *node_port,
+                *network_router_port,
+                *speed,
+                *include_dropped,
+            ).await
+        },
         Some(("setup", _setup_matches)) => setup::execute(),
+        Some(("sniff", sniff_matches)) => {
+            let network_router_port = sniff_matches.get_one::<u16>("NETWORK_ROUTER_PORT").unwrap();
+            let get_many = |id: &str| sniff_matches.get_many::<String>(id)
+                .unwrap_or_default()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>();
+            let filters = sniff::Filters {
+                nodes: get_many("NODE_NAME"),
+                processes: get_many("PROCESS"),
+                packages: get_many("PACKAGE"),
+            };
+
+            sniff::execute(*network_router_port, filters).await
+        },
+        Some(("snapshot", snapshot_matches)) => match snapshot_matches.subcommand() {
+            Some(("save", save_matches)) => {
+                let home = PathBuf::from(save_matches.get_one::<String>("HOME").unwrap());
+                let name = save_matches.get_one::<String>("NAME").unwrap();
+                snapshot::save(&home, name)
+            },
+            Some(("restore", restore_matches)) => {
+                let home = PathBuf::from(restore_matches.get_one::<String>("HOME").unwrap());
+                let name = restore_matches.get_one::<String>("NAME").unwrap();
+                snapshot::restore(name, &home)
+            },
+            Some(("list", _list_matches)) => snapshot::list(),
+            _ => unreachable!(),
+        },
         Some(("start-package", start_package_matches)) => {
             let package_dir = PathBuf::from(start_package_matches.get_one::<String>("DIR").unwrap());
-            let url: String = match start_package_matches.get_one::<String>("URL") {
-                Some(url) => url.clone(),
-                None => {
-                    let port = start_package_matches.get_one::<u16>("NODE_PORT").unwrap();
-                    format!("http://localhost:{}", port)
-                },
-            };
-            start_package::execute(&package_dir, &url).await
+            let url = get_node_url(start_package_matches, config);
+            match workspace::find_members(&package_dir, &get_member_names(start_package_matches))? {
+                Some(members) => workspace::start(&members, &url).await,
+                None => start_package::execute(&package_dir, &url).await,
+            }
         },
         Some(("update", update_matches)) => {
             let args = update_matches.get_many::<String>("ARGUMENTS")
@@ -290,6 +485,16 @@ async fn execute(
 
             update::execute(args, branch)
         },
+        Some(("watch", watch_matches)) => {
+            let package_dir = PathBuf::from(watch_matches.get_one::<String>("DIR").unwrap());
+            let no_ui = watch_matches.get_one::<bool>("NO_UI").unwrap();
+            let verbose = !watch_matches.get_one::<bool>("QUIET").unwrap();
+            let url = get_node_url(watch_matches, config);
+            let skip_deps_check = watch_matches.get_one::<bool>("SKIP_DEPS_CHECK").unwrap();
+            let options = get_build_options(watch_matches, config)?;
+
+            watch::execute(&package_dir, *no_ui, verbose, &url, *skip_deps_check, &options).await
+        },
         _ => {
             warn!("Invalid subcommand. Usage:\n{}", usage);
             Ok(())
@@ -297,7 +502,47 @@ async fn execute(
     }
 }
 
-async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
+/// The package dir given on the command line, else cwd: where to start looking for `kit.toml`
+async fn get_config_start_dir(current_dir: &std::ffi::OsString) -> anyhow::Result<PathBuf> {
+    // Only `DIR` is needed here, so skip fetching the remote runtime releases
+    let matches = make_app(current_dir, &KitConfig::default(), false).await?.try_get_matches();
+    Ok(matches
+        .ok()
+        .and_then(|matches| {
+            let (_, subcommand_matches) = matches.subcommand()?;
+            subcommand_matches.try_get_one::<String>("DIR").ok().flatten().map(PathBuf::from)
+        })
+        .unwrap_or_else(|| PathBuf::from(current_dir)))
+}
+
+/// If `is_fetch_releases`, offer the latest remote runtime releases as
+///  `boot-fake-node --version`s; else accept any version
+async fn make_app(
+    current_dir: &std::ffi::OsString,
+    config: &KitConfig,
+    is_fetch_releases: bool,
+) -> anyhow::Result<Command> {
+    // Defaults, possibly overridden by kit config
+    let node_port = config.node.port.unwrap_or(8080).to_string();
+    let network_router_port = config.fake_node.network_router_port.unwrap_or(9001).to_string();
+    let fake_node_home = config.fake_node.home.clone()
+        .unwrap_or_else(|| PathBuf::from("/tmp/kinode-fake-node"))
+        .into_os_string();
+    let fake_node_name = config.fake_node.name.clone().unwrap_or_else(|| "fake.os".to_string());
+    let password = config.fake_node.password.clone().unwrap_or_else(|| "secret".to_string());
+    let runtime_version = config.fake_node.version.clone().unwrap_or_else(|| "latest".to_string());
+    let publisher = config.new.publisher.clone().unwrap_or_else(|| "template.os".to_string());
+    let language = config.new.language.clone().unwrap_or_else(|| "rust".to_string());
+    let tests_config = config.tests.config.clone()
+        .unwrap_or_else(|| PathBuf::from("tests.toml"))
+        .into_os_string();
+    let no_ui = config.build.no_ui.to_string();
+    let quiet = config.build.quiet.to_string();
+    let skip_deps_check = config.build.skip_deps_check.to_string();
+    let profile = config.build.profile.clone().unwrap_or_else(|| "release".to_string());
+    let wasm_opt = config.build.wasm_opt.to_string();
+    let strip = config.build.strip.to_string();
+
     Ok(command!()
         .name("kit")
         .version(env!("CARGO_PKG_VERSION"))
@@ -326,8 +571,8 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('v')
                 .long("version")
                 .help("Version of Kinode binary to use (overridden by --runtime-path)")
-                .default_value("latest")
-                .value_parser(PossibleValuesParser::new({
+                .default_value(runtime_version.clone())
+                .value_parser(if is_fetch_releases {
                     let mut possible_values = vec!["latest".to_string()];
                     let mut remote_values = boot_fake_node::find_releases_with_asset_if_online(
                         None,
@@ -336,15 +581,24 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                     ).await?;
                     remote_values.truncate(MAX_REMOTE_VALUES);
                     possible_values.append(&mut remote_values);
-                    possible_values
-                }))
+                    // The configured version is always allowed (e.g. an older
+                    //  release, or any release when offline): it is checked
+                    //  when its binary is fetched
+                    if !possible_values.contains(&runtime_version) {
+                        possible_values.push(runtime_version.clone());
+                    }
+                    PossibleValuesParser::new(possible_values).into()
+                } else {
+                    value_parser!(String)
+                })
             )
             .arg(Arg::new("NODE_PORT")
                 .action(ArgAction::Set)
                 .short('p')
                 .long("port")
                 .help("The port to run the fake node on")
-                .default_value("8080")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
                 .value_parser(value_parser!(u16))
             )
             .arg(Arg::new("HOME")
@@ -352,20 +606,22 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('h')
                 .long("home")
                 .help("Where to place the home directory for the fake node")
-                .default_value("/tmp/kinode-fake-node")
+                .default_value(fake_node_home)
+                .env("KIT_FAKE_NODE_HOME")
             )
             .arg(Arg::new("NODE_NAME")
                 .action(ArgAction::Set)
                 .short('f')
                 .long("fake-node-name")
                 .help("Name for fake node")
-                .default_value("fake.os")
+                .default_value(fake_node_name)
             )
             .arg(Arg::new("NETWORK_ROUTER_PORT")
                 .action(ArgAction::Set)
                 .long("network-router-port")
                 .help("The port to run the network router on (or to connect to)")
-                .default_value("9001")
+                .default_value(network_router_port.clone())
+                .env("KIT_NETWORK_ROUTER_PORT")
                 .value_parser(value_parser!(u16))
             )
             .arg(Arg::new("RPC_ENDPOINT")
@@ -390,7 +646,7 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .action(ArgAction::Set)
                 .long("password")
                 .help("Password to login")
-                .default_value("secret")
+                .default_value(password)
             )
             .arg(Arg::new("RELEASE")
                 .action(ArgAction::SetTrue)
@@ -398,6 +654,38 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .help("If set and given --runtime-path, compile release build [default: debug build]")
                 .required(false)
             )
+            .arg(Arg::new("RECORD")
+                .action(ArgAction::Set)
+                .long("record")
+                .help("Record all network router traffic to this path (msgpack; JSON Lines to PATH.jsonl)")
+                .required(false)
+            )
+            .arg(Arg::new("TOPOLOGY")
+                .action(ArgAction::Set)
+                .long("topology")
+                .help("Path to a TOML file declaring a network router and fake nodes to boot (overrides --count)")
+                .required(false)
+            )
+            .arg(Arg::new("COUNT")
+                .action(ArgAction::Set)
+                .long("count")
+                .help("Number of fake nodes to boot; if >1, nodes are named after --fake-node-name with an index, use consecutive ports, and are placed under --home")
+                .default_value("1")
+                .value_parser(value_parser!(u16).range(1..))
+            )
+            .arg(Arg::new("DETACH")
+                .action(ArgAction::SetTrue)
+                .short('d')
+                .long("detach")
+                .help("If set, run node(s) in the background and return; manage them with `kit nodes`")
+                .required(false)
+            )
+            .arg(Arg::new("BACKGROUND")
+                .action(ArgAction::SetTrue)
+                .long("background")
+                .hide(true)
+                .required(false)
+            )
             .arg(Arg::new("help")
                 .long("help")
                 .action(ArgAction::Help)
@@ -409,14 +697,18 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
             .visible_alias("b")
             .arg(Arg::new("DIR")
                 .action(ArgAction::Set)
-                .help("The package directory to build")
+                .help("The package or workspace directory to build")
                 .default_value(current_dir)
             )
             .arg(Arg::new("NO_UI")
-                .action(ArgAction::SetTrue)
+                .action(ArgAction::Set)
                 .long("no-ui")
-                .help("If set, do NOT build the web UI for the process; no-op if passed with UI_ONLY")
-                .required(false)
+                .help("If set, do NOT build the web UI for the process; no-op if passed with UI_ONLY (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(no_ui.clone())
             )
             .arg(Arg::new("UI_ONLY")
                 .action(ArgAction::SetTrue)
@@ -425,17 +717,81 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .required(false)
             )
             .arg(Arg::new("QUIET")
-                .action(ArgAction::SetTrue)
+                .action(ArgAction::Set)
                 .short('q')
                 .long("quiet")
-                .help("If set, do not print build stdout/stderr")
-                .required(false)
+                .help("If set, do not print build stdout/stderr (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(quiet.clone())
             )
             .arg(Arg::new("SKIP_DEPS_CHECK")
-                .action(ArgAction::SetTrue)
+                .action(ArgAction::Set)
                 .short('s')
                 .long("skip-deps-check")
-                .help("If set, do not check for dependencies")
+                .help("If set, do not check for dependencies (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(skip_deps_check.clone())
+            )
+            .arg(Arg::new("FORCE")
+                .action(ArgAction::SetTrue)
+                .short('f')
+                .long("force")
+                .help("If set, rebuild all processes even if unchanged since the last build")
+                .required(false)
+            )
+            .arg(Arg::new("JOBS")
+                .action(ArgAction::Set)
+                .short('j')
+                .long("jobs")
+                .help("Number of processes to build in parallel [default: number of CPUs]")
+                .value_parser(value_parser!(usize))
+                .required(false)
+            )
+            .arg(Arg::new("PROFILE")
+                .action(ArgAction::Set)
+                .long("profile")
+                .help("Build profile: `size` optimizes Rust processes for size, stripping them and running wasm-opt")
+                .value_parser(["debug", "release", "size"])
+                .default_value(profile.clone())
+            )
+            .arg(Arg::new("WASM_OPT")
+                .action(ArgAction::Set)
+                .long("wasm-opt")
+                .help("If set, optimize Rust processes with wasm-opt (requires binaryen) (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(wasm_opt.clone())
+            )
+            .arg(Arg::new("STRIP")
+                .action(ArgAction::Set)
+                .long("strip")
+                .help("If set, strip debug info from Rust processes (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(strip.clone())
+            )
+            .arg(Arg::new("MESSAGE_FORMAT")
+                .action(ArgAction::Set)
+                .long("message-format")
+                .help("`json` prints one JSON object per build step and toolchain command (start, finish, error with diagnostics) to stdout, and logs to stderr")
+                .value_parser(["human", "json"])
+                .default_value("human")
+            )
+            .arg(Arg::new("MEMBER")
+                .action(ArgAction::Append)
+                .short('m')
+                .long("member")
+                .help("If DIR is a workspace, only this member (package dir name); may be repeated")
                 .required(false)
             )
         )
@@ -444,7 +800,7 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
             .visible_alias("bs")
             .arg(Arg::new("DIR")
                 .action(ArgAction::Set)
-                .help("The package directory to build")
+                .help("The package or workspace directory to build")
                 .default_value(current_dir)
             )
             .arg(Arg::new("NODE_PORT")
@@ -452,7 +808,8 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('p')
                 .long("port")
                 .help("Node port: for use on localhost (overridden by URL)")
-                .default_value("8080")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
                 .value_parser(value_parser!(u16))
             )
             .arg(Arg::new("URL")
@@ -460,13 +817,18 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('u')
                 .long("url")
                 .help("Node URL (overrides NODE_PORT)")
+                .env("KIT_NODE_URL")
                 .required(false)
             )
             .arg(Arg::new("NO_UI")
-                .action(ArgAction::SetTrue)
+                .action(ArgAction::Set)
                 .long("no-ui")
-                .help("If set, do NOT build the web UI for the process; no-op if passed with UI_ONLY")
-                .required(false)
+                .help("If set, do NOT build the web UI for the process; no-op if passed with UI_ONLY (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(no_ui.clone())
             )
             .arg(Arg::new("UI_ONLY")
                 .action(ArgAction::SetTrue)
@@ -475,20 +837,92 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .required(false)
             )
             .arg(Arg::new("QUIET")
-                .action(ArgAction::SetTrue)
+                .action(ArgAction::Set)
                 .short('q')
                 .long("quiet")
-                .help("If set, do not print build stdout/stderr")
-                .required(false)
+                .help("If set, do not print build stdout/stderr (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(quiet.clone())
             )
             .arg(Arg::new("SKIP_DEPS_CHECK")
-                .action(ArgAction::SetTrue)
+                .action(ArgAction::Set)
                 .short('s')
                 .long("skip-deps-check")
-                .help("If set, do not check for dependencies")
+                .help("If set, do not check for dependencies (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(skip_deps_check.clone())
+            )
+            .arg(Arg::new("FORCE")
+                .action(ArgAction::SetTrue)
+                .short('f')
+                .long("force")
+                .help("If set, rebuild all processes even if unchanged since the last build")
+                .required(false)
+            )
+            .arg(Arg::new("JOBS")
+                .action(ArgAction::Set)
+                .short('j')
+                .long("jobs")
+                .help("Number of processes to build in parallel [default: number of CPUs]")
+                .value_parser(value_parser!(usize))
+                .required(false)
+            )
+            .arg(Arg::new("PROFILE")
+                .action(ArgAction::Set)
+                .long("profile")
+                .help("Build profile: `size` optimizes Rust processes for size, stripping them and running wasm-opt")
+                .value_parser(["debug", "release", "size"])
+                .default_value(profile.clone())
+            )
+            .arg(Arg::new("WASM_OPT")
+                .action(ArgAction::Set)
+                .long("wasm-opt")
+                .help("If set, optimize Rust processes with wasm-opt (requires binaryen) (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(wasm_opt.clone())
+            )
+            .arg(Arg::new("STRIP")
+                .action(ArgAction::Set)
+                .long("strip")
+                .help("If set, strip debug info from Rust processes (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(strip.clone())
+            )
+            .arg(Arg::new("MESSAGE_FORMAT")
+                .action(ArgAction::Set)
+                .long("message-format")
+                .help("`json` prints one JSON object per build step and toolchain command (start, finish, error with diagnostics) to stdout, and logs to stderr")
+                .value_parser(["human", "json"])
+                .default_value("human")
+            )
+            .arg(Arg::new("MEMBER")
+                .action(ArgAction::Append)
+                .short('m')
+                .long("member")
+                .help("If DIR is a workspace, only this member (package dir name); may be repeated")
                 .required(false)
             )
         )
+        .subcommand(Command::new("check")
+            .about("Validate a Kinode package's pkg/manifest.json and pkg/metadata.json")
+            .arg(Arg::new("DIR")
+                .action(ArgAction::Set)
+                .help("The package directory to check")
+                .default_value(current_dir)
+            )
+        )
         .subcommand(Command::new("dev-ui")
             .about("Start the web UI development server with hot reloading (same as `cd ui && npm i && npm start`)")
             .visible_alias("d")
@@ -502,7 +936,8 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('p')
                 .long("port")
                 .help("Node port: for use on localhost (overridden by URL)")
-                .default_value("8080")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
                 .value_parser(value_parser!(u16))
             )
             .arg(Arg::new("URL")
@@ -510,14 +945,19 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('u')
                 .long("url")
                 .help("Node URL (overrides NODE_PORT)")
+                .env("KIT_NODE_URL")
                 .required(false)
             )
             .arg(Arg::new("SKIP_DEPS_CHECK")
-                .action(ArgAction::SetTrue)
+                .action(ArgAction::Set)
                 .short('s')
                 .long("skip-deps-check")
-                .help("If set, do not check for dependencies")
-                .required(false)
+                .help("If set, do not check for dependencies (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(skip_deps_check.clone())
             )
         )
         .subcommand(Command::new("inject-message")
@@ -538,7 +978,8 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('p')
                 .long("port")
                 .help("Node port: for use on localhost (overridden by URL)")
-                .default_value("8080")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
                 .value_parser(value_parser!(u16))
             )
             .arg(Arg::new("URL")
@@ -546,6 +987,7 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('u')
                 .long("url")
                 .help("Node URL (overrides NODE_PORT)")
+                .env("KIT_NODE_URL")
                 .required(false)
             )
             .arg(Arg::new("NODE_NAME")
@@ -588,15 +1030,16 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('u')
                 .long("publisher")
                 .help("Name of the publisher")
-                .default_value("template.os")
+                .default_value(publisher)
+                .env("KIT_PUBLISHER")
             )
             .arg(Arg::new("LANGUAGE")
                 .action(ArgAction::Set)
                 .short('l')
                 .long("language")
                 .help("Programming language of the template")
-                .value_parser(["rust", "python", "javascript"])
-                .default_value("rust")
+                .value_parser(["rust", "python", "javascript", "typescript"])
+                .default_value(language)
             )
             .arg(Arg::new("TEMPLATE")
                 .action(ArgAction::Set)
@@ -613,13 +1056,200 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .required(false)
             )
         )
+        .subcommand(Command::new("nodes")
+            .about("Manage fake nodes booted with `kit boot-fake-node --detach`")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(Command::new("list")
+                .about("List detached nodes")
+            )
+            .subcommand(Command::new("stop")
+                .about("Stop a detached node")
+                .arg(Arg::new("NODE_NAME")
+                    .action(ArgAction::Set)
+                    .help("Name of the node to stop")
+                    .required(true)
+                )
+            )
+            .subcommand(Command::new("logs")
+                .about("Print the output of a detached node")
+                .arg(Arg::new("NODE_NAME")
+                    .action(ArgAction::Set)
+                    .help("Name of the node")
+                    .required(true)
+                )
+                .arg(Arg::new("FOLLOW")
+                    .action(ArgAction::SetTrue)
+                    .short('f')
+                    .long("follow")
+                    .help("If set, keep printing new output until the node exits")
+                    .required(false)
+                )
+            )
+        )
+        .subcommand(Command::new("package")
+            .about("Build a Kinode package if changed, then write its zip and a metadata & hash summary, without a node")
+            .arg(Arg::new("DIR")
+                .action(ArgAction::Set)
+                .help("The package directory to package")
+                .default_value(current_dir)
+            )
+            .arg(Arg::new("OUTPUT")
+                .action(ArgAction::Set)
+                .short('o')
+                .long("output")
+                .help("Path to write the zip to; the summary is written beside it as `.build-info.json` [default: target/<package>:<publisher>.zip]")
+                .required(false)
+            )
+            .arg(Arg::new("NO_UI")
+                .action(ArgAction::Set)
+                .long("no-ui")
+                .help("If set, do NOT build the web UI for the process (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(no_ui.clone())
+            )
+            .arg(Arg::new("QUIET")
+                .action(ArgAction::Set)
+                .short('q')
+                .long("quiet")
+                .help("If set, do not print build stdout/stderr (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(quiet.clone())
+            )
+            .arg(Arg::new("SKIP_DEPS_CHECK")
+                .action(ArgAction::Set)
+                .short('s')
+                .long("skip-deps-check")
+                .help("If set, do not check for dependencies (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(skip_deps_check.clone())
+            )
+            .arg(Arg::new("FORCE")
+                .action(ArgAction::SetTrue)
+                .short('f')
+                .long("force")
+                .help("If set, rebuild all processes even if unchanged since the last build")
+                .required(false)
+            )
+            .arg(Arg::new("JOBS")
+                .action(ArgAction::Set)
+                .short('j')
+                .long("jobs")
+                .help("Number of processes to build in parallel [default: number of CPUs]")
+                .value_parser(value_parser!(usize))
+                .required(false)
+            )
+            .arg(Arg::new("PROFILE")
+                .action(ArgAction::Set)
+                .long("profile")
+                .help("Build profile: `size` optimizes Rust processes for size, stripping them and running wasm-opt")
+                .value_parser(["debug", "release", "size"])
+                .default_value(profile.clone())
+            )
+            .arg(Arg::new("WASM_OPT")
+                .action(ArgAction::Set)
+                .long("wasm-opt")
+                .help("If set, optimize Rust processes with wasm-opt (requires binaryen) (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(wasm_opt.clone())
+            )
+            .arg(Arg::new("STRIP")
+                .action(ArgAction::Set)
+                .long("strip")
+                .help("If set, strip debug info from Rust processes (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(strip.clone())
+            )
+        )
+        .subcommand(Command::new("publish")
+            .about("Add or update a built Kinode package in a node's app store; its listing (description, image, website) comes only from the zipped pkg/metadata.json")
+            .arg(Arg::new("DIR")
+                .action(ArgAction::Set)
+                .help("The package directory to publish")
+                .default_value(current_dir)
+            )
+            .arg(Arg::new("NODE_PORT")
+                .action(ArgAction::Set)
+                .short('p')
+                .long("port")
+                .help("Node port: for use on localhost (overridden by URL)")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
+                .value_parser(value_parser!(u16))
+            )
+            .arg(Arg::new("URL")
+                .action(ArgAction::Set)
+                .short('u')
+                .long("url")
+                .help("Node URL (overrides NODE_PORT)")
+                .env("KIT_NODE_URL")
+                .required(false)
+            )
+            .arg(Arg::new("NO_MIRROR")
+                .action(ArgAction::SetTrue)
+                .long("no-mirror")
+                .help("If set, do not mirror the package to other nodes (stops mirroring an already-published package)")
+                .required(false)
+            )
+            .arg(Arg::new("INSTALL")
+                .action(ArgAction::SetTrue)
+                .long("install")
+                .help("If set, also install the package on the node")
+                .required(false)
+            )
+        )
         .subcommand(Command::new("run-tests")
             .about("Run Kinode tests")
             .visible_alias("t")
             .arg(Arg::new("PATH")
                 .action(ArgAction::Set)
                 .help("Path to tests configuration file")
-                .default_value("tests.toml")
+                .default_value(tests_config)
+                .env("KIT_TESTS_CONFIG")
+            )
+            .arg(Arg::new("REPORT")
+                .action(ArgAction::Append)
+                .long("report")
+                .help("Write a test report as `junit=<path>` or `json=<path>` (repeatable)")
+                .value_parser(|s: &str| s.parse::<run_tests::report::Report>().map_err(|e| e.to_string()))
+                .required(false)
+            )
+            .arg(Arg::new("KEEP_GOING")
+                .action(ArgAction::SetTrue)
+                .short('k')
+                .long("keep-going")
+                .help("If set, run all tests even after failures, then summarize")
+                .required(false)
+            )
+            .arg(Arg::new("FILTER")
+                .action(ArgAction::Set)
+                .long("filter")
+                .help("Only run tests whose name or test package path matches this regex")
+                .value_parser(|s: &str| regex::Regex::new(s).map_err(|e| e.to_string()))
+                .required(false)
+            )
+            .arg(Arg::new("JOBS")
+                .action(ArgAction::Set)
+                .short('j')
+                .long("jobs")
+                .help("Number of tests to run in parallel; if >1, node & router ports and node homes are allocated automatically")
+                .default_value("1")
+                .value_parser(value_parser!(usize))
             )
         )
         .subcommand(Command::new("remove-package")
@@ -648,7 +1278,8 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('p')
                 .long("port")
                 .help("Node port: for use on localhost (overridden by URL)")
-                .default_value("8080")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
                 .value_parser(value_parser!(u16))
             )
             .arg(Arg::new("URL")
@@ -656,19 +1287,148 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('u')
                 .long("url")
                 .help("Node URL (overrides NODE_PORT)")
+                .env("KIT_NODE_URL")
                 .required(false)
                 //.default_value("http://localhost:8080")
             )
         )
+        .subcommand(Command::new("replay")
+            .about("Replay a network router recording into fresh fake nodes")
+            .arg(Arg::new("RECORDING")
+                .action(ArgAction::Set)
+                .help("Path to recording (msgpack) made with `--record` or `record` in tests.toml")
+                .required(true)
+            )
+            .arg(Arg::new("PATH")
+                .action(ArgAction::Set)
+                .short('r')
+                .long("runtime-path")
+                .help("Path to Kinode core repo or runtime binary (overrides --version)")
+            )
+            .arg(Arg::new("VERSION")
+                .action(ArgAction::Set)
+                .short('v')
+                .long("version")
+                .help("Version of Kinode binary to use (overridden by --runtime-path)")
+                .default_value(runtime_version.clone())
+            )
+            .arg(Arg::new("RELEASE")
+                .action(ArgAction::SetTrue)
+                .long("release")
+                .help("If set and given --runtime-path, compile release build [default: debug build]")
+                .required(false)
+            )
+            .arg(Arg::new("HOME")
+                .action(ArgAction::Set)
+                .long("home")
+                .help("Directory to place the fake node homes in (one subdirectory per node)")
+                .default_value("/tmp/kinode-replay")
+            )
+            .arg(Arg::new("NODE_PORT")
+                .action(ArgAction::Set)
+                .short('p')
+                .long("port")
+                .help("Port of the first fake node; subsequent nodes use the following ports")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
+                .value_parser(value_parser!(u16))
+            )
+            .arg(Arg::new("NETWORK_ROUTER_PORT")
+                .action(ArgAction::Set)
+                .long("network-router-port")
+                .help("The port to run the network router on")
+                .default_value(network_router_port.clone())
+                .env("KIT_NETWORK_ROUTER_PORT")
+                .value_parser(value_parser!(u16))
+            )
+            .arg(Arg::new("SPEED")
+                .action(ArgAction::Set)
+                .long("speed")
+                .help("Replay speed multiplier relative to the recorded timing")
+                .default_value("1.0")
+                .value_parser(value_parser!(f64))
+            )
+            .arg(Arg::new("INCLUDE_DROPPED")
+                .action(ArgAction::SetTrue)
+                .long("include-dropped")
+                .help("If set, also inject messages the router dropped during recording")
+                .required(false)
+            )
+        )
         .subcommand(Command::new("setup")
             .about("Fetch & setup kit dependencies")
         )
+        .subcommand(Command::new("sniff")
+            .about("Stream messages passing through a fake node network router")
+            .arg(Arg::new("NETWORK_ROUTER_PORT")
+                .action(ArgAction::Set)
+                .long("network-router-port")
+                .help("The port the network router is running on")
+                .default_value(network_router_port.clone())
+                .env("KIT_NETWORK_ROUTER_PORT")
+                .value_parser(value_parser!(u16))
+            )
+            .arg(Arg::new("NODE_NAME")
+                .action(ArgAction::Append)
+                .short('n')
+                .long("node")
+                .help("Only show messages to or from this node (repeatable)")
+                .required(false)
+            )
+            .arg(Arg::new("PROCESS")
+                .action(ArgAction::Append)
+                .long("process")
+                .help("Only show messages to or from this process name (repeatable)")
+                .required(false)
+            )
+            .arg(Arg::new("PACKAGE")
+                .action(ArgAction::Append)
+                .short('a')
+                .long("package")
+                .help("Only show messages to or from this package name (repeatable)")
+                .required(false)
+            )
+        )
+        .subcommand(Command::new("snapshot")
+            .about("Save or restore fake node state")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(Command::new("save")
+                .about("Save the state of a (stopped) fake node")
+                .arg(Arg::new("HOME")
+                    .action(ArgAction::Set)
+                    .help("Home directory of the node to save")
+                    .required(true)
+                )
+                .arg(Arg::new("NAME")
+                    .action(ArgAction::Set)
+                    .help("Name of the snapshot; overwrites an existing snapshot of the same name")
+                    .required(true)
+                )
+            )
+            .subcommand(Command::new("restore")
+                .about("Replace the state of a (stopped) fake node with a snapshot")
+                .arg(Arg::new("HOME")
+                    .action(ArgAction::Set)
+                    .help("Home directory of the node to restore into")
+                    .required(true)
+                )
+                .arg(Arg::new("NAME")
+                    .action(ArgAction::Set)
+                    .help("Name of the snapshot")
+                    .required(true)
+                )
+            )
+            .subcommand(Command::new("list")
+                .about("List saved snapshots")
+            )
+        )
         .subcommand(Command::new("start-package")
             .about("Start a built Kinode process")
             .visible_alias("s")
             .arg(Arg::new("DIR")
                 .action(ArgAction::Set)
-                .help("The package directory to build")
+                .help("The package or workspace directory to start")
                 .default_value(current_dir)
             )
             .arg(Arg::new("NODE_PORT")
@@ -676,7 +1436,8 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('p')
                 .long("port")
                 .help("Node port: for use on localhost (overridden by URL)")
-                .default_value("8080")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
                 .value_parser(value_parser!(u16))
             )
             .arg(Arg::new("URL")
@@ -684,6 +1445,14 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .short('u')
                 .long("url")
                 .help("Node URL (overrides NODE_PORT)")
+                .env("KIT_NODE_URL")
+                .required(false)
+            )
+            .arg(Arg::new("MEMBER")
+                .action(ArgAction::Append)
+                .short('m')
+                .long("member")
+                .help("If DIR is a workspace, only this member (package dir name); may be repeated")
                 .required(false)
             )
         )
@@ -701,6 +1470,99 @@ async fn make_app(current_dir: &std::ffi::OsString) -> anyhow::Result<Command> {
                 .default_value("master")
             )
         )
+        .subcommand(Command::new("watch")
+            .about("Build and start a Kinode package, then rebuild and restart it on every change")
+            .visible_alias("w")
+            .arg(Arg::new("DIR")
+                .action(ArgAction::Set)
+                .help("The package directory to watch")
+                .default_value(current_dir)
+            )
+            .arg(Arg::new("NODE_PORT")
+                .action(ArgAction::Set)
+                .short('p')
+                .long("port")
+                .help("Node port: for use on localhost (overridden by URL)")
+                .default_value(node_port.clone())
+                .env("KIT_NODE_PORT")
+                .value_parser(value_parser!(u16))
+            )
+            .arg(Arg::new("URL")
+                .action(ArgAction::Set)
+                .short('u')
+                .long("url")
+                .help("Node URL (overrides NODE_PORT)")
+                .env("KIT_NODE_URL")
+                .required(false)
+            )
+            .arg(Arg::new("NO_UI")
+                .action(ArgAction::Set)
+                .long("no-ui")
+                .help("If set, do NOT build the web UI for the process (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(no_ui.clone())
+            )
+            .arg(Arg::new("QUIET")
+                .action(ArgAction::Set)
+                .short('q')
+                .long("quiet")
+                .help("If set, do not print build stdout/stderr (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(quiet.clone())
+            )
+            .arg(Arg::new("SKIP_DEPS_CHECK")
+                .action(ArgAction::Set)
+                .short('s')
+                .long("skip-deps-check")
+                .help("If set, do not check for dependencies (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(skip_deps_check.clone())
+            )
+            .arg(Arg::new("JOBS")
+                .action(ArgAction::Set)
+                .short('j')
+                .long("jobs")
+                .help("Number of processes to build in parallel [default: number of CPUs]")
+                .value_parser(value_parser!(usize))
+                .required(false)
+            )
+            .arg(Arg::new("PROFILE")
+                .action(ArgAction::Set)
+                .long("profile")
+                .help("Build profile: `size` optimizes Rust processes for size, stripping them and running wasm-opt")
+                .value_parser(["debug", "release", "size"])
+                .default_value(profile.clone())
+            )
+            .arg(Arg::new("WASM_OPT")
+                .action(ArgAction::Set)
+                .long("wasm-opt")
+                .help("If set, optimize Rust processes with wasm-opt (requires binaryen) (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(wasm_opt.clone())
+            )
+            .arg(Arg::new("STRIP")
+                .action(ArgAction::Set)
+                .long("strip")
+                .help("If set, strip debug info from Rust processes (`=false` to override kit config)")
+                .value_parser(value_parser!(bool))
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("true")
+                .default_value(strip.clone())
+            )
+        )
     )
 }
 
@@ -709,15 +1571,22 @@ async fn main() -> anyhow::Result<()> {
     let log_path = std::env::var("KIT_LOG_PATH")
         .unwrap_or_else(|_| KIT_LOG_PATH_DEFAULT.to_string());
     let log_path = PathBuf::from(log_path);
-    let _guard = init_tracing(log_path)?;
+    let guard = init_tracing(log_path)?;
     let current_dir = env::current_dir()?.into_os_string();
-    let mut app = make_app(&current_dir).await?;
+    let config = config::load(&get_config_start_dir(&current_dir).await?)?;
+    let mut app = make_app(&current_dir, &config, true).await?;
 
     let usage = app.render_usage();
     let matches = app.get_matches();
     let matches = matches.subcommand();
+    if let Some((_, subcommand_matches)) = matches {
+        let message_format = subcommand_matches.try_get_one::<String>("MESSAGE_FORMAT").ok().flatten();
+        if message_format.map(|f| f.as_str()) == Some("json") {
+            IS_STDOUT_LOG_TO_STDERR.store(true, Ordering::Relaxed);
+        }
+    }
 
-    let _result = match execute(usage, matches).await {
+    let result = match execute(usage, matches, &config).await {
         Ok(()) => Ok(()),
         Err(e) => {
             // TODO: add more non-"nerdview" error messages here
@@ -726,7 +1595,6 @@ async fn main() -> anyhow::Result<()> {
                 Some(e) => {
                     if e.is_connect() {
                         error!("kit: error connecting; is Kinode running?");
-                        return Ok(());
                     }
                 },
             }
@@ -747,9 +1615,14 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
-    // Return Ok(()) here rather than result above because
+    // Exit non-zero rather than return result above because
     //  #[tracing::instrument(err)] already outputs errors
     //  that occur more locally to the error site (i.e.
     //  with function information).
+    if result.is_err() {
+        // Flush logs first: `exit` skips destructors
+        drop(guard);
+        std::process::exit(1);
+    }
     Ok(())
 }
@@ -1,14 +1,15 @@
 import { componentize } from '@bytecodealliance/componentize-js';
 import { readFile, writeFile } from 'node:fs/promises';
 
-// Retrieve the package name from command line arguments
+// Retrieve the package name (and, optionally, the source path) from command line arguments
 const processName = process.argv[2];
 if (!processName) {
-    console.error('Please provide a process name (e.g. `node componentize.mjs process_name`).');
+    console.error('Please provide a process name (e.g. `node componentize.mjs process_name [src/lib.js]`).');
     process.exit(1);
 }
+const sourcePath = process.argv[3] ?? 'src/lib.js';
 
-const jsSource = await readFile('src/lib.js', 'utf8');
+const jsSource = await readFile(sourcePath, 'utf8');
 const witPath = 'wit/kinode.wit';
 
 const { component } = await componentize(jsSource, { witPath: witPath, worldName: 'process', debug: false });
@@ -9,6 +9,7 @@ pub enum Language {
     Rust,
     Python,
     Javascript,
+    Typescript,
 }
 
 #[derive(Clone)]
@@ -25,6 +26,7 @@ impl Language {
             Language::Rust => "rust",
             Language::Python => "python",
             Language::Javascript => "javascript",
+            Language::Typescript => "typescript",
         }.to_string()
     }
 }
@@ -46,7 +48,8 @@ impl From<&String> for Language {
             "rust" => Language::Rust,
             "python" => Language::Python,
             "javascript" => Language::Javascript,
-            _ => panic!("kit: language must be 'rust' or 'python'; not '{s}'"),
+            "typescript" => Language::Typescript,
+            _ => panic!("kit: language must be 'rust', 'python', 'javascript' or 'typescript'; not '{s}'"),
         }
     }
 }
@@ -183,7 +186,7 @@ pub fn execute(
         ));
     }
     match language {
-        Language::Javascript => {
+        Language::Javascript | Language::Typescript => {
             path_to_content.insert(
                 format!("{}/{}", package_name, PATH_TO_CONTENT[0].0),
                 replace_vars(PATH_TO_CONTENT[0].1, &package_name, &publisher),
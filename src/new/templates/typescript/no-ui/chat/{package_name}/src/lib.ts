@@ -0,0 +1,103 @@
+import { printToTerminal, receive, sendAndAwaitResponse, sendResponse } from "kinode:process/standard@0.7.0";
+
+type MessageArchive = { [node: string]: string };
+
+type ChatRequest =
+    | { Send: { target: string, message: string } }
+    | { History: null };
+
+function parseAddress(addressString: string): { node: string, process: string, packageName: string, publisher: string } {
+    const [node, rest] = addressString.split('@');
+    const [process, packageName, publisher] = rest.split(':');
+    return { node, process, packageName, publisher };
+}
+
+function inputBytesToString(byteObject: Uint8Array): { bytes: Uint8Array, string: string } {
+    // Determine the size of the Uint8Array
+    const size = Object.keys(byteObject).length;
+    const byteArray = new Uint8Array(size);
+
+    // Assign the bytes to the array
+    for (let i = 0; i < size; i++) {
+        byteArray[i] = byteObject[i];
+    }
+
+    // Convert the Uint8Array to a string
+    const string = new TextDecoder().decode(byteArray);
+
+    return {bytes: byteArray, string: string};
+}
+
+function handleMessage(ourNode: string, messageArchive: MessageArchive): MessageArchive {
+    const [source, message] = receive();
+
+    if (message.tag == 'response') {
+        throw new Error(`unexpected Response: ${JSON.stringify(message.val)}`);
+    } else if (message.tag == 'request') {
+        const { bytes: bodyBytes, string: body0 } = inputBytesToString(message.val.body);
+        const body: ChatRequest = JSON.parse(body0);
+        const encoder = new TextEncoder();
+        if ('Send' in body) {
+            const { target, message: messageText } = body.Send;
+            if (target === ourNode) {
+                printToTerminal(0, `{package_name}|${source.node}: ${messageText}`);
+                messageArchive[source.node] = messageText;
+            } else {
+                sendAndAwaitResponse(
+                    {
+                        node: target,
+                        process: {
+                            processName: "{package_name}",
+                            packageName: "{package_name}",
+                            publisherNode: "{publisher}"
+                        }
+                    },
+                    {
+                        inherit: false,
+                        expectsResponse: 5,
+                        body: bodyBytes,
+                        metadata: null
+                    },
+                    null
+                );
+            }
+            sendResponse(
+                {
+                    inherit: false,
+                    body: encoder.encode(JSON.stringify({ Ack: null })),
+                    metadata: null,
+                    capabilities: [],
+                },
+                null
+            );
+        } else if ('History' in body) {
+            sendResponse(
+                {
+                    inherit: false,
+                    body: encoder.encode(JSON.stringify({ History: { messages: messageArchive } })),
+                    metadata: null,
+                    capabilities: [],
+                },
+                null
+            );
+        } else {
+            throw new Error(`Unexpected Request: ${body0}`)
+        }
+    }
+    return messageArchive;
+}
+
+export function init(our: string): void {
+    printToTerminal(0, `{package_name}: begin (typescript)`);
+
+    const { node: ourNode } = parseAddress(our);
+    let messageArchive: MessageArchive = {};
+
+    while (true) {
+        try {
+            messageArchive = handleMessage(ourNode, messageArchive);
+        } catch (error) {
+            printToTerminal(0, `{package_name}: got error ${error}`);
+        }
+    }
+}
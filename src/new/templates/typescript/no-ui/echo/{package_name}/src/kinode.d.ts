@@ -0,0 +1,52 @@
+// Types for the parts of the kinode process API used by this process;
+//  implemented by the runtime and bound by `componentize.mjs`
+declare module "kinode:process/standard@0.7.0" {
+    export interface ProcessId {
+        processName: string;
+        packageName: string;
+        publisherNode: string;
+    }
+
+    export interface Address {
+        node: string;
+        process: ProcessId;
+    }
+
+    export interface Capability {
+        issuer: Address;
+        params: string;
+    }
+
+    export interface Request {
+        inherit: boolean;
+        expectsResponse: number | bigint | null;
+        body: Uint8Array;
+        metadata: string | null;
+        capabilities?: Capability[];
+    }
+
+    export interface Response {
+        inherit: boolean;
+        body: Uint8Array;
+        metadata: string | null;
+        capabilities: Capability[];
+    }
+
+    export interface LazyLoadBlob {
+        mime: string | null;
+        bytes: Uint8Array;
+    }
+
+    export type Message =
+        | { tag: "request"; val: Request }
+        | { tag: "response"; val: [Response, Uint8Array | null] };
+
+    export function printToTerminal(verbosity: number, message: string): void;
+    export function receive(): [Address, Message];
+    export function sendResponse(response: Response, blob: LazyLoadBlob | null): void;
+    export function sendAndAwaitResponse(
+        target: Address,
+        request: Request,
+        blob: LazyLoadBlob | null,
+    ): [Address, Message];
+}
@@ -0,0 +1,231 @@
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument, warn};
+
+use super::build;
+
+const NODES_DIR: &str = "nodes";
+const STOP_TIMEOUT_SECONDS: u64 = 10;
+const FOLLOW_POLL_MILLISECONDS: u64 = 250;
+
+/// Written by `kit boot-fake-node --detach` for each node it boots
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodeState {
+    pub name: String,
+    pub pid: u32,
+    pub port: u16,
+    pub home: PathBuf,
+    pub log_path: PathBuf,
+    pub pty_path: Option<PathBuf>,
+    pub network_router_port: u16,
+    /// The background `kit` process that owns the node and cleans up after it
+    pub supervisor_pid: u32,
+}
+
+impl NodeState {
+    pub fn is_running(&self) -> bool {
+        is_process_running(self.pid)
+    }
+}
+
+fn is_process_running(pid: u32) -> bool {
+    nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as i32), None).is_ok()
+}
+
+pub fn get_nodes_dir() -> anyhow::Result<PathBuf> {
+    let nodes_dir = PathBuf::from(build::CACHE_DIR).join(NODES_DIR);
+    fs::create_dir_all(&nodes_dir)?;
+    Ok(nodes_dir)
+}
+
+fn get_state_path(name: &str) -> anyhow::Result<PathBuf> {
+    Ok(get_nodes_dir()?.join(format!("{}.json", name)))
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub fn write_state(state: &NodeState) -> anyhow::Result<()> {
+    let mut file = fs::File::create(get_state_path(&state.name)?)?;
+    file.write_all(serde_json::to_string_pretty(state)?.as_bytes())?;
+    Ok(())
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub fn read_state(name: &str) -> anyhow::Result<Option<NodeState>> {
+    let state_path = get_state_path(name)?;
+    if !state_path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&fs::read_to_string(state_path)?)?))
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub fn remove_state(name: &str) -> anyhow::Result<()> {
+    let state_path = get_state_path(name)?;
+    if state_path.exists() {
+        fs::remove_file(state_path)?;
+    }
+    Ok(())
+}
+
+/// Remove the state of node `name` if it is still that of the node with
+///  `pid` owned by this supervisor, rather than of a node since re-booted
+#[instrument(level = "trace", err, skip_all)]
+pub fn remove_own_state(name: &str, pid: u32) -> anyhow::Result<()> {
+    match read_state(name)? {
+        Some(state) if state.pid == pid && state.supervisor_pid == std::process::id() => {
+            remove_state(name)
+        },
+        _ => Ok(()),
+    }
+}
+
+fn get_stop_request_path(name: &str) -> anyhow::Result<PathBuf> {
+    Ok(get_nodes_dir()?.join(format!("{}.stop", name)))
+}
+
+/// Whether `kit nodes stop` has asked the supervisor to stop node `name`
+///  with `pid`; if so, the request is consumed
+#[instrument(level = "trace", err, skip_all)]
+pub fn take_stop_request(name: &str, pid: u32) -> anyhow::Result<bool> {
+    let stop_request_path = get_stop_request_path(name)?;
+    let Ok(requested_pid) = fs::read_to_string(&stop_request_path) else {
+        return Ok(false);
+    };
+    if requested_pid.trim() != pid.to_string() {
+        return Ok(false);
+    }
+    fs::remove_file(stop_request_path)?;
+    Ok(true)
+}
+
+#[instrument(level = "trace", err, skip_all)]
+fn read_states() -> anyhow::Result<Vec<NodeState>> {
+    let mut states = Vec::new();
+    for entry in fs::read_dir(get_nodes_dir()?)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match serde_json::from_str(&fs::read_to_string(&path)?) {
+            Ok(state) => states.push(state),
+            Err(e) => warn!("Skipping unreadable node state {:?}: {}", path, e),
+        }
+    }
+    states.sort_by(|a: &NodeState, b| a.name.cmp(&b.name));
+    Ok(states)
+}
+
+fn read_existing_state(name: &str) -> anyhow::Result<NodeState> {
+    read_state(name)?.ok_or_else(|| anyhow::anyhow!(
+        "kit nodes: no node named {}; see `kit nodes list`",
+        name,
+    ))
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub fn list() -> anyhow::Result<()> {
+    let states = read_states()?;
+    if states.is_empty() {
+        info!("No detached nodes.");
+        return Ok(());
+    }
+    info!(
+        "{:<24} {:<8} {:<7} {:<8} {}",
+        "NAME", "STATUS", "PORT", "PID", "HOME",
+    );
+    for state in states {
+        info!(
+            "{:<24} {:<8} {:<7} {:<8} {:?}",
+            state.name,
+            if state.is_running() { "running" } else { "exited" },
+            state.port,
+            state.pid,
+            state.home,
+        );
+    }
+    Ok(())
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub async fn stop(name: &str) -> anyhow::Result<()> {
+    let state = read_existing_state(name)?;
+    if !state.is_running() {
+        info!("{} is not running; removing its state.", name);
+        return remove_state(name);
+    }
+
+    // SIGINT is unreliable for pty-attached nodes, so, as in cleanup, the
+    //  supervisor sends Ctrl+C through the node's pty; it then reaps the node
+    //  and removes its state
+    let pid = nix::unistd::Pid::from_raw(state.pid as i32);
+    if is_process_running(state.supervisor_pid) {
+        fs::write(get_stop_request_path(name)?, state.pid.to_string())?;
+    } else {
+        warn!("Supervisor of {} (pid {}) is gone; killing the node.", name, state.supervisor_pid);
+        nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL)?;
+    }
+    for _ in 0..STOP_TIMEOUT_SECONDS * 4 {
+        if !state.is_running() {
+            info!("Stopped {}.", name);
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+
+    warn!("{} (pid {}) did not stop within {}s; killing it.", name, state.pid, STOP_TIMEOUT_SECONDS);
+    nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL)?;
+    for _ in 0..STOP_TIMEOUT_SECONDS * 4 {
+        if !state.is_running() {
+            info!("Killed {}.", name);
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    Err(anyhow::anyhow!(
+        "kit nodes: {} (pid {}) did not stop, even when killed",
+        name,
+        state.pid,
+    ))
+}
+
+fn copy_to_stdout(file: &mut fs::File) -> anyhow::Result<()> {
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    let mut stdout = std::io::stdout();
+    stdout.write_all(&buffer)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub async fn logs(name: &str, follow: bool) -> anyhow::Result<()> {
+    let state = read_existing_state(name)?;
+    let mut file = open_log(&state.log_path)?;
+    copy_to_stdout(&mut file)?;
+    if !follow {
+        return Ok(());
+    }
+
+    // Tail the log until the node exits
+    loop {
+        let position = file.stream_position()?;
+        if file.metadata()?.len() < position {
+            // Truncated: start again from the top
+            file.seek(SeekFrom::Start(0))?;
+        }
+        copy_to_stdout(&mut file)?;
+        if !state.is_running() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(FOLLOW_POLL_MILLISECONDS)).await;
+    }
+}
+
+fn open_log(log_path: &Path) -> anyhow::Result<fs::File> {
+    fs::File::open(log_path)
+        .map_err(|e| anyhow::anyhow!("kit nodes: couldn't open log {:?}: {}", log_path, e))
+}
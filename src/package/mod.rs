@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+use tracing::{info, instrument};
+
+use super::build;
+use super::check;
+use super::start_package;
+
+/// Build the package (if changed), validate it & zip its `pkg/` to
+///  `output` (by default as `start-package` does, in `target/`), with a
+///  `.build-info.json` summary of its metadata & hashes beside the zip
+#[instrument(level = "trace", err, skip_all)]
+pub async fn execute(
+    package_dir: &Path,
+    output: Option<&Path>,
+    no_ui: bool,
+    verbose: bool,
+    skip_deps_check: bool,
+    options: &build::BuildOptions,
+) -> anyhow::Result<()> {
+    // Building validates the package
+    build::execute(package_dir, no_ui, false, verbose, skip_deps_check, options).await?;
+    let metadata = check::read_metadata(package_dir)?;
+
+    let zip_path = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| start_package::get_zip_path(package_dir, &metadata));
+    let zip_hash = start_package::zip_package(package_dir, &metadata, &zip_path)?;
+    info!(
+        "Packaged {}:{} v{}.{}.{} to {:?} (sha256 {}); summary in {:?}",
+        metadata.package,
+        metadata.publisher,
+        metadata.version[0],
+        metadata.version[1],
+        metadata.version[2],
+        zip_path,
+        zip_hash,
+        zip_path.with_extension("build-info.json"),
+    );
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+#[allow(deprecated)]
+use base64::decode;
+use serde_json::json;
+use tracing::{info, instrument};
+
+use super::check;
+use super::inject_message;
+use super::start_package;
+
+fn format_version(version: &[u32; 3]) -> String {
+    format!("{}.{}.{}", version[0], version[1], version[2])
+}
+
+/// The version in the `pkg/metadata.json` of the package currently added to
+///  the app store of the node at `url`, if any
+#[instrument(level = "trace", err, skip_all)]
+async fn get_published_version(
+    url: &str,
+    metadata: &check::Metadata,
+) -> anyhow::Result<Option<[u32; 3]>> {
+    let request = inject_message::make_message(
+        "vfs:distro:sys",
+        Some(15),
+        &json!({
+            "path": format!("/{}:{}/pkg/metadata.json", metadata.package, metadata.publisher),
+            "action": "Read",
+        }).to_string(),
+        None,
+        None,
+        None,
+    )?;
+    let response = inject_message::send_request(url, request).await?;
+    let inject_message::Response { ref body, ref lazy_load_blob, .. } =
+        inject_message::parse_response(response).await?;
+    let body = serde_json::from_str::<serde_json::Value>(body)?;
+    // Any error means the package has not been added
+    if body != json!("Read") {
+        return Ok(None);
+    }
+    let Some(blob) = lazy_load_blob else {
+        return Ok(None);
+    };
+    #[allow(deprecated)]
+    let published: serde_json::Value = serde_json::from_slice(&decode(blob)?)?;
+    let version = serde_json::from_value(published["version"].clone())
+        .map_err(|e| anyhow::anyhow!("kit publish: couldn't read published version: {}", e))?;
+    Ok(Some(version))
+}
+
+/// Add the package to, or update it in, the app store of the node at `url`,
+///  requiring its version to be newer than that published, and set whether
+///  it is mirrored; then install it if `install`. The listing (description,
+///  image, website) is read by the app store from the zipped
+///  `pkg/metadata.json`: there is no separate request to set it
+#[instrument(level = "trace", err, skip_all)]
+pub async fn execute(
+    package_dir: &Path,
+    url: &str,
+    mirror: bool,
+    install: bool,
+) -> anyhow::Result<()> {
+    let metadata = check::validate(package_dir)?;
+    let package_name = metadata.package.as_str();
+    let publisher = metadata.publisher.as_str();
+    let pkg_publisher = format!("{}:{}", package_name, publisher);
+
+    let published_version = get_published_version(url, &metadata).await?;
+    match published_version {
+        Some(published) if published >= metadata.version => {
+            return Err(anyhow::anyhow!(
+                "kit publish: {} v{} is published on node at {}; bump `version` in pkg/metadata.json (now v{}) to publish",
+                pkg_publisher,
+                format_version(&published),
+                url,
+                format_version(&metadata.version),
+            ));
+        },
+        Some(published) => info!(
+            "Updating {} from v{} to v{}...",
+            pkg_publisher,
+            format_version(&published),
+            format_version(&metadata.version),
+        ),
+        None => info!("Publishing {} v{}...", pkg_publisher, format_version(&metadata.version)),
+    }
+
+    let zip_filename = start_package::get_zip_path(package_dir, &metadata);
+    let zip_hash = start_package::zip_package(package_dir, &metadata, &zip_filename)?;
+
+    let new_pkg_request = start_package::new_package(
+        None,
+        package_name,
+        publisher,
+        zip_filename.to_str().unwrap(),
+        mirror,
+    )?;
+    let response = inject_message::send_request(url, new_pkg_request).await?;
+    let inject_message::Response { ref body, .. } = inject_message::parse_response(response).await?;
+    let body = serde_json::from_str::<serde_json::Value>(body)?;
+    if body.get("NewPackageResponse") != Some(&json!("Success")) {
+        return Err(anyhow::anyhow!("Failed to publish package. Got response from node: {}", body));
+    }
+    // `NewPackage` sets mirroring only for new listings
+    if published_version.is_some() {
+        let request_type = if mirror { "StartMirroring" } else { "StopMirroring" };
+        let mirror_request = start_package::interact_with_package(request_type, None, package_name, publisher)?;
+        let response = inject_message::send_request(url, mirror_request).await?;
+        let inject_message::Response { ref body, .. } = inject_message::parse_response(response).await?;
+        let body = serde_json::from_str::<serde_json::Value>(body)?;
+        if body.get("MirrorResponse") != Some(&json!("Success")) {
+            return Err(anyhow::anyhow!("Failed to {} package. Got response from node: {}", request_type, body));
+        }
+    }
+    info!(
+        "Published {} v{} (sha256 {}) on node at {}; {}{}",
+        pkg_publisher,
+        format_version(&metadata.version),
+        zip_hash,
+        url,
+        if mirror { "mirroring" } else { "not mirroring" },
+        metadata.description
+            .as_ref()
+            .map(|description| format!("\n  {}", description))
+            .unwrap_or_default(),
+    );
+
+    if install {
+        let install_request = start_package::interact_with_package("Install", None, package_name, publisher)?;
+        let response = inject_message::send_request(url, install_request).await?;
+        let inject_message::Response { ref body, .. } = inject_message::parse_response(response).await?;
+        let body = serde_json::from_str::<serde_json::Value>(body)?;
+        if body.get("InstallResponse") != Some(&json!("Success")) {
+            return Err(anyhow::anyhow!("Failed to install package. Got response from node: {}", body));
+        }
+        info!("Installed {} on node at {}", pkg_publisher, url);
+    }
+    Ok(())
+}
@@ -1,9 +1,9 @@
-use std::fs;
 use std::path::Path;
 use std::process;
 
 use tracing::instrument;
 
+use super::check;
 use super::inject_message;
 use super::start_package::interact_with_package;
 
@@ -17,13 +17,8 @@ pub async fn execute(
     let (package_name, publisher): (String, String) = match (arg_package_name, arg_publisher) {
         (Some(package_name), Some(publisher)) => (package_name.into(), publisher.into()),
         _ => {
-            let pkg_dir = project_dir.join("pkg").canonicalize()?;
-            let metadata: serde_json::Value = serde_json::from_reader(fs::File::open(pkg_dir
-                .join("metadata.json")
-            )?)?;
-            let package_name = metadata["package"].as_str().unwrap();
-            let publisher = metadata["publisher"].as_str().unwrap();
-            (package_name.into(), publisher.into())
+            let metadata = check::read_metadata(project_dir)?;
+            (metadata.package, metadata.publisher)
         },
     };
 
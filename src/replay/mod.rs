@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use futures_util::SinkExt;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Duration, Instant};
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message::{Binary, Text}};
+use tracing::{info, instrument};
+
+use super::boot_fake_node::{get_runtime_path, run_runtime};
+use super::run_tests::cleanup::{cleanup, cleanup_on_signal};
+use super::run_tests::network_router::{self, read_recording, RecordedMessage};
+use super::run_tests::types::*;
+use super::run_tests::wait_until_booted;
+
+const REPLAY_IDENTIFIER: &str = "kit-replay";
+
+#[instrument(level = "trace", err, skip_all)]
+async fn inject_recording(
+    network_router_port: u16,
+    recorded_messages: &[RecordedMessage],
+    speed: f64,
+) -> anyhow::Result<()> {
+    let (mut ws_stream, _) = connect_async(
+        format!("ws://127.0.0.1:{}", network_router_port),
+    ).await?;
+    ws_stream.send(Text(REPLAY_IDENTIFIER.to_string())).await?;
+
+    let Some(first) = recorded_messages.first() else {
+        return Ok(());
+    };
+    let first_timestamp_ms = first.timestamp_ms;
+    let start = Instant::now();
+
+    info!("Replaying {} messages...", recorded_messages.len());
+    for recorded_message in recorded_messages {
+        let offset_ms = recorded_message.timestamp_ms.saturating_sub(first_timestamp_ms);
+        let offset = Duration::from_millis((offset_ms as f64 / speed) as u64);
+        let elapsed = start.elapsed();
+        if offset > elapsed {
+            sleep(offset - elapsed).await;
+        }
+        ws_stream.send(Binary(rmp_serde::to_vec(&recorded_message.kernel_message)?)).await?;
+    }
+    info!("Done replaying messages.");
+
+    Ok(())
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub async fn execute(
+    recording_path: &Path,
+    runtime_path: Option<PathBuf>,
+    version: String,
+    release: bool,
+    home_prefix: PathBuf,
+    node_port: u16,
+    network_router_port: u16,
+    speed: f64,
+    include_dropped: bool,
+) -> anyhow::Result<()> {
+    if speed <= 0.0 {
+        return Err(anyhow::anyhow!("kit replay: --speed must be positive; got {}", speed));
+    }
+    let recorded_messages: Vec<RecordedMessage> = read_recording(recording_path)?
+        .into_iter()
+        .filter(|rm| include_dropped || !rm.dropped)
+        .collect();
+    let node_names: BTreeSet<String> = recorded_messages
+        .iter()
+        .flat_map(|rm| [rm.source.clone(), rm.target.clone()])
+        .collect();
+    if node_names.is_empty() {
+        return Err(anyhow::anyhow!("kit replay: recording {:?} contains no messages", recording_path));
+    }
+
+    let detached = true;
+    let runtime_path = get_runtime_path(runtime_path, &version, release).await?;
+
+    let mut task_handles = Vec::new();
+    let node_handles = Arc::new(Mutex::new(Vec::new()));
+    let node_cleanup_infos = Arc::new(Mutex::new(Vec::new()));
+
+    let (send_to_cleanup, recv_in_cleanup) = tokio::sync::mpsc::unbounded_channel();
+    let (send_to_kill, _recv_kill) = tokio::sync::broadcast::channel(1);
+    let recv_kill_in_cos = send_to_kill.subscribe();
+    let recv_kill_in_router = send_to_kill.subscribe();
+
+    let node_cleanup_infos_for_cleanup = Arc::clone(&node_cleanup_infos);
+    let node_handles_for_cleanup = Arc::clone(&node_handles);
+    let send_to_kill_for_cleanup = send_to_kill.clone();
+    let handle = tokio::spawn(cleanup(
+        recv_in_cleanup,
+        send_to_kill_for_cleanup,
+        node_cleanup_infos_for_cleanup,
+        Some(node_handles_for_cleanup),
+        detached,
+        true,
+    ));
+    task_handles.push(handle);
+    let send_to_cleanup_for_signal = send_to_cleanup.clone();
+    let handle = tokio::spawn(cleanup_on_signal(send_to_cleanup_for_signal, recv_kill_in_cos));
+    task_handles.push(handle);
+    let _cleanup_context = CleanupContext::new(send_to_cleanup.clone());
+
+    let network_router_for_router = NetworkRouter::new(network_router_port);
+    let handle = tokio::spawn(async move {
+        let _ = network_router::execute(
+            network_router_for_router,
+            recv_kill_in_router,
+        ).await;
+    });
+    task_handles.push(handle);
+
+    // TODO: can remove?
+    std::thread::sleep(std::time::Duration::from_secs(1));
+
+    let mut ports = Vec::new();
+    for (i, node_name) in node_names.iter().enumerate() {
+        let node_home = home_prefix.join(node_name);
+        if node_home.exists() {
+            fs::remove_dir_all(&node_home)?;
+        }
+        fs::create_dir_all(&node_home)?;
+        let port = node_port + i as u16;
+
+        let (runtime_process, master_fd, _) = run_runtime(
+            &runtime_path,
+            &node_home,
+            port,
+            network_router_port,
+            &["--fake-node-name", node_name],
+            false,
+            detached,
+            None,
+        )?;
+
+        let mut node_cleanup_infos = node_cleanup_infos.lock().await;
+        node_cleanup_infos.push(NodeCleanupInfo {
+            master_fd,
+            process_id: runtime_process.id() as i32,
+            home: node_home,
+        });
+        let mut node_handles = node_handles.lock().await;
+        node_handles.push(runtime_process);
+        ports.push(port);
+    }
+
+    for (node_name, port) in node_names.iter().zip(ports.iter()) {
+        info!("Setting up node {} on port {}...", node_name, port);
+        let recv_kill_in_wait = send_to_kill.subscribe();
+        wait_until_booted(*port, 5, recv_kill_in_wait).await?;
+    }
+
+    inject_recording(network_router_port, &recorded_messages, speed).await?;
+    info!("Nodes are still running for inspection; Ctrl+C to exit.");
+
+    for handle in task_handles {
+        handle.await.unwrap();
+    }
+
+    Ok(())
+}
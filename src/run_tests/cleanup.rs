@@ -57,7 +57,8 @@ pub async fn cleanup(
             //  did not work properly for detached processes; specifically
             //  for `run-tests` that exited early by, e.g., a user input
             //  Ctrl+C.
-            nix::unistd::write(master_fd.as_raw_fd(), b"\x03").unwrap();
+            // Ignore failure: the process may have already exited
+            let _ = nix::unistd::write(master_fd.as_raw_fd(), b"\x03");
         } else {
             let pid = nix::unistd::Pid::from_raw(*process_id);
             match nix::sys::wait::waitpid(pid, Some(nix::sys::wait::WaitPidFlag::WNOHANG)) {
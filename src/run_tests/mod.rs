@@ -1,25 +1,42 @@
+use std::collections::HashMap;
 use std::fs;
+use std::net::TcpListener;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use dirs::home_dir;
-use tokio::sync::Mutex;
-use tokio::time::{sleep, Duration};
-use tracing::{debug, info, instrument};
+use futures_util::StreamExt;
+use regex::Regex;
+use tokio::sync::{Mutex, OnceCell};
+use tokio::time::{sleep, Duration, Instant};
+use tracing::{debug, info, instrument, warn};
 
-use super::boot_fake_node::{compile_runtime, get_runtime_binary, run_runtime};
+use super::boot_fake_node::{compile_runtime, get_runtime_binary, run_runtime, NODE_LOG_FILE_NAME};
 use super::build;
 use super::inject_message;
+use super::snapshot;
 use super::start_package;
 
 pub mod cleanup;
 use cleanup::{cleanup, cleanup_on_signal};
 pub mod network_router;
+pub mod report;
+use report::{log_summary, write_reports, Report};
 pub mod types;
 use types::*;
 mod tester_types;
 use tester_types as tt;
 
+const ISOLATED_HOMES_DIR: &str = "kinode-run-tests";
+
+/// Package path -> its build during this run, shared by the tests using it
+type BuiltPackages = Mutex<HashMap<PathBuf, Arc<OnceCell<()>>>>;
+
+/// Ports allocated to a test, kept bound until the router or node that
+///  will use each is started, so that nothing else can take them
+type ReservedPorts = HashMap<u16, TcpListener>;
+
 fn get_basename(file_path: &Path) -> Option<&str> {
     file_path
         .file_name()
@@ -89,7 +106,7 @@ impl Config {
 }
 
 #[instrument(level = "trace", err, skip_all)]
-async fn wait_until_booted(
+pub async fn wait_until_booted(
     port: u16,
     max_waits: u16,
     mut recv_kill_in_wait: BroadcastRecvBool,
@@ -270,7 +287,7 @@ async fn run_tests(
             match serde_json::from_str(body)? {
                 tt::TesterResponse::Pass => info!("PASS"),
                 tt::TesterResponse::Fail { test, file, line, column } => {
-                    return Err(anyhow::anyhow!("FAIL: {} {}:{}:{}", test, file, line, column));
+                    return Err(TestFailure { test, file, line, column }.into());
                 },
                 tt::TesterResponse::GetFullMessage(_) => {
                     return Err(anyhow::anyhow!("FAIL: Unexpected Response"));
@@ -285,13 +302,47 @@ async fn run_tests(
     Ok(())
 }
 
+fn read_node_logs(nodes: &[Node], node_names: Vec<String>) -> Vec<NodeLog> {
+    nodes
+        .iter()
+        .zip(node_names)
+        .map(|(node, node_name)| NodeLog {
+            node: node_name,
+            home: node.home.clone(),
+            log: fs::read_to_string(node.home.join(NODE_LOG_FILE_NAME)).unwrap_or_default(),
+        })
+        .collect()
+}
+
 #[instrument(level = "trace", err, skip_all)]
-async fn handle_test(detached: bool, runtime_path: &Path, test: Test) -> anyhow::Result<()> {
-    for setup_package_path in &test.setup_package_paths {
-        build::execute(&setup_package_path, false, false, test.package_build_verbose, false).await?;
-    }
-    for TestPackage { ref path, .. } in &test.test_packages {
-        build::execute(path, false, false, test.package_build_verbose, false).await?;
+async fn handle_test(
+    detached: bool,
+    runtime_path: &Path,
+    test: Test,
+    built_packages: &BuiltPackages,
+    mut reserved_ports: ReservedPorts,
+) -> anyhow::Result<()> {
+    let package_paths = test.setup_package_paths
+        .iter()
+        .chain(test.test_packages.iter().map(|tp| &tp.path));
+    let build_options = build::BuildOptions { jobs: 1, ..Default::default() };
+    for package_path in package_paths {
+        // Lock only to claim the package's build: concurrent tests wait on
+        //  the same package, but build different packages in parallel
+        let built_package = built_packages
+            .lock()
+            .await
+            .entry(package_path.clone())
+            .or_default()
+            .clone();
+        built_package.get_or_try_init(|| build::execute(
+            package_path,
+            false,
+            false,
+            test.package_build_verbose,
+            false,
+            &build_options,
+        )).await?;
     }
 
     // Initialize variables for master node and nodes list
@@ -322,30 +373,30 @@ async fn handle_test(detached: bool, runtime_path: &Path, test: Test) -> anyhow:
     task_handles.push(handle);
     let _cleanup_context = CleanupContext::new(send_to_cleanup.clone());
 
-    let network_router_port_for_router = test.network_router.port.clone();
-    let network_router_defects_for_router = test.network_router.defects.clone();
+    let network_router_for_router = test.network_router.clone();
+    reserved_ports.remove(&test.network_router.port);
     let handle = tokio::spawn(async move {
         let _ = network_router::execute(
-            network_router_port_for_router,
-            network_router_defects_for_router,
+            network_router_for_router,
             recv_kill_in_router,
         ).await;
     });
     task_handles.push(handle);
 
     // TODO: can remove?
-    std::thread::sleep(std::time::Duration::from_secs(1));
+    sleep(Duration::from_secs(1)).await;
 
     // Process each node
     for node in &test.nodes {
         fs::create_dir_all(&node.home)?;
         let node_home = fs::canonicalize(&node.home)?;
-        for dir in &["kernel", "kv", "sqlite", "vfs"] {
-            let dir = node_home.join(dir);
-            if dir.exists() {
-                fs::remove_dir_all(&node_home.join(dir)).unwrap();
-            }
-        }
+        // Copying node state is blocking: keep it off the runtime's workers
+        let snapshot = node.snapshot.clone();
+        let home = node_home.clone();
+        tokio::task::spawn_blocking(move || match snapshot {
+            Some(ref snapshot) => snapshot::restore(snapshot, &home),
+            None => snapshot::remove_node_state(&home),
+        }).await??;
 
         let mut args = vec!["--fake-node-name", &node.fake_node_name];
         if let Some(ref rpc) = node.rpc {
@@ -358,7 +409,13 @@ async fn handle_test(detached: bool, runtime_path: &Path, test: Test) -> anyhow:
             args.extend_from_slice(&["--testnet"]);
         }
 
-        let (runtime_process, master_fd) = run_runtime(
+        let log_path = node_home.join(NODE_LOG_FILE_NAME);
+        if log_path.exists() {
+            fs::remove_file(&log_path)?;
+        }
+
+        reserved_ports.remove(&node.port);
+        let (runtime_process, master_fd, _) = run_runtime(
             &runtime_path,
             &node_home,
             node.port,
@@ -366,6 +423,7 @@ async fn handle_test(detached: bool, runtime_path: &Path, test: Test) -> anyhow:
             &args[..],
             node.runtime_verbose,
             detached,
+            Some(&log_path),
         )?;
 
         let mut node_cleanup_infos = node_cleanup_infos.lock().await;
@@ -416,8 +474,92 @@ async fn handle_test(detached: bool, runtime_path: &Path, test: Test) -> anyhow:
     Ok(())
 }
 
+/// Bind a free port, keeping it bound in `reserved_ports`
+fn allocate_port(reserved_ports: &mut ReservedPorts) -> anyhow::Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    reserved_ports.insert(port, listener);
+    Ok(port)
+}
+
+/// Give each test its own free ports & node homes (under the returned dir)
+///  so that tests can run concurrently; node home basenames (i.e. node
+///  names) are kept. Each test's ports stay reserved until it uses them
+fn isolate_tests(tests: &mut [Test]) -> anyhow::Result<(PathBuf, Vec<ReservedPorts>)> {
+    let homes_dir = std::env::temp_dir()
+        .join(ISOLATED_HOMES_DIR)
+        .join(format!("{}", std::process::id()));
+    let mut reserved_ports = Vec::new();
+    for (i, test) in tests.iter_mut().enumerate() {
+        let mut test_reserved_ports = ReservedPorts::new();
+        test.network_router.port = allocate_port(&mut test_reserved_ports)?;
+        for node in test.nodes.iter_mut() {
+            node.port = allocate_port(&mut test_reserved_ports)?;
+            let basename = get_basename(&node.home).ok_or_else(|| anyhow::anyhow!(
+                "run_tests:isolate_tests: did not find basename for {:?}",
+                node.home,
+            ))?;
+            node.home = homes_dir.join(format!("{}", i)).join(basename);
+        }
+        reserved_ports.push(test_reserved_ports);
+    }
+    Ok((homes_dir, reserved_ports))
+}
+
+/// Select tests whose name or any test package path matches `filter`
+fn is_selected(test: &Test, filter: &Regex) -> bool {
+    filter.is_match(&test.name())
+        || test.test_packages.iter().any(|tp| filter.is_match(&tp.path.to_string_lossy()))
+}
+
+/// Run a single test, returning its result for reporting alongside
+///  the error (if any) that caused it to not pass
+async fn run_test(
+    detached: bool,
+    runtime_path: &Path,
+    test: Test,
+    built_packages: &BuiltPackages,
+    reserved_ports: ReservedPorts,
+) -> anyhow::Result<(TestResult, anyhow::Result<()>)> {
+    let start = Instant::now();
+    let mut result = TestResult {
+        name: test.name(),
+        duration_secs: 0.0,
+        outcome: TestOutcome::Pass,
+        setup_package_paths: test.setup_package_paths.clone(),
+        test_package_paths: test.test_packages.iter().map(|tp| tp.path.clone()).collect(),
+        node_logs: vec![],
+    };
+    let nodes = test.nodes.clone();
+    let node_names = make_node_names(test.nodes.clone())?;
+
+    info!("Running test {}...", result.name);
+    let test_result = handle_test(detached, runtime_path, test, built_packages, reserved_ports).await;
+
+    result.duration_secs = start.elapsed().as_secs_f64();
+    result.node_logs = read_node_logs(&nodes, node_names);
+    if let Err(ref e) = test_result {
+        result.outcome = match e.downcast_ref::<TestFailure>() {
+            Some(TestFailure { test, file, line, column }) => TestOutcome::Fail {
+                test: test.clone(),
+                file: file.clone(),
+                line: *line,
+                column: *column,
+            },
+            None => TestOutcome::Error { message: format!("{:?}", e) },
+        };
+    }
+    Ok((result, test_result))
+}
+
 #[instrument(level = "trace", err, skip_all)]
-pub async fn execute(config_path: &str) -> anyhow::Result<()> {
+pub async fn execute(
+    config_path: &str,
+    reports: Vec<Report>,
+    keep_going: bool,
+    filter: Option<Regex>,
+    jobs: usize,
+) -> anyhow::Result<()> {
     let detached = true; // TODO: to arg?
 
     let config_content = fs::read_to_string(config_path)?;
@@ -454,8 +596,108 @@ pub async fn execute(config_path: &str) -> anyhow::Result<()> {
         },
     };
 
-    for test in config.tests {
-        handle_test(detached, &runtime_path, test).await?;
+    let tests: Vec<Test> = config.tests
+        .into_iter()
+        .filter(|test| filter.as_ref().map(|f| is_selected(test, f)).unwrap_or(true))
+        .collect();
+    if tests.is_empty() {
+        warn!("kit run-tests: no tests selected.");
+        return Ok(());
+    }
+
+    let mut tests = tests;
+    let (homes_dir, reserved_ports) = if jobs > 1 {
+        let (homes_dir, reserved_ports) = isolate_tests(&mut tests)?;
+        (Some(homes_dir), reserved_ports)
+    } else {
+        (None, vec![])
+    };
+    let mut reserved_ports = reserved_ports.into_iter();
+
+    // Once a test fails, do not start any more unless `keep_going`;
+    //  tests already running are allowed to finish & clean up
+    let stop = Arc::new(AtomicBool::new(false));
+    let built_packages: Arc<BuiltPackages> = Arc::new(Mutex::new(HashMap::new()));
+    let runtime_path = Arc::new(runtime_path);
+    // Each test runs in its own task so that tests run in parallel
+    let outcomes: Vec<_> = futures_util::stream::iter(tests)
+        .map(|test| {
+            let (stop, built_packages, runtime_path) =
+                (Arc::clone(&stop), Arc::clone(&built_packages), Arc::clone(&runtime_path));
+            let reserved_ports = reserved_ports.next().unwrap_or_default();
+            // Report a test whose task panics as an error, like any other
+            let panic_result = TestResult {
+                name: test.name(),
+                duration_secs: 0.0,
+                outcome: TestOutcome::Pass,
+                setup_package_paths: test.setup_package_paths.clone(),
+                test_package_paths: test.test_packages.iter().map(|tp| tp.path.clone()).collect(),
+                node_logs: vec![],
+            };
+            let stop_on_panic = Arc::clone(&stop);
+            let handle = tokio::spawn(async move {
+                if stop.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let outcome = run_test(detached, &runtime_path, test, &built_packages, reserved_ports).await;
+                if !keep_going && !matches!(outcome, Ok((_, Ok(())))) {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                Some(outcome)
+            });
+            async move {
+                handle.await.unwrap_or_else(|e| {
+                    if !keep_going {
+                        stop_on_panic.store(true, Ordering::Relaxed);
+                    }
+                    let message = format!("kit run-tests: test task panicked: {}", e);
+                    Some(Ok((
+                        TestResult {
+                            outcome: TestOutcome::Error { message: message.clone() },
+                            ..panic_result
+                        },
+                        Err(anyhow::anyhow!(message)),
+                    )))
+                })
+            }
+        })
+        .buffered(jobs.max(1))
+        .collect()
+        .await;
+
+    if let Some(homes_dir) = homes_dir {
+        if let Err(e) = fs::remove_dir_all(&homes_dir) {
+            warn!("kit run-tests: couldn't remove node homes {:?}: {}", homes_dir, e);
+        }
+    }
+
+    let mut results = Vec::new();
+    let mut first_error = None;
+    for outcome in outcomes.into_iter().flatten() {
+        let (result, test_result) = outcome?;
+        results.push(result);
+        if let Err(e) = test_result {
+            if !keep_going && first_error.is_none() {
+                first_error = Some(e);
+            }
+        }
+    }
+    write_reports(&reports, config_path, &results)?;
+    log_summary(&results);
+
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+    let num_failed = results
+        .iter()
+        .filter(|r| !matches!(r.outcome, TestOutcome::Pass))
+        .count();
+    if num_failed > 0 {
+        return Err(anyhow::anyhow!(
+            "kit run-tests: {} of {} tests failed",
+            num_failed,
+            results.len(),
+        ));
     }
 
     Ok(())
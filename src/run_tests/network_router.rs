@@ -1,11 +1,19 @@
 use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use futures_util::{SinkExt, StreamExt};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tokio::task;
+use tokio::time::{sleep, Duration, Instant};
 use tokio_tungstenite::{accept_async, tungstenite::protocol::Message::{Binary, Text}, WebSocketStream};
-use tracing::{info, error, instrument};
+use tracing::{info, error, instrument, warn};
 
 use crate::run_tests::types::*;
 use crate::run_tests::tester_types as tt;
@@ -20,6 +28,83 @@ struct Connection {
 
 type Connections = HashMap<String, Connection>;
 
+/// Connections whose handshake identifier starts with this prefix are
+///  observers: they receive a copy of every routed message (as a
+///  msgpack-encoded `RecordedMessage`) rather than being routed to
+pub const OBSERVER_IDENTIFIER_PREFIX: &str = "kit-observer";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedMessage {
+    /// Milliseconds since the Unix epoch at which the router received the message
+    pub timestamp_ms: u64,
+    pub source: String,
+    pub target: String,
+    /// Whether the message was dropped by a defect, partition, or link rule
+    pub dropped: bool,
+    pub kernel_message: tt::KernelMessage,
+}
+
+impl RecordedMessage {
+    fn new(kernel_message: &tt::KernelMessage, dropped: bool) -> anyhow::Result<Self> {
+        Ok(RecordedMessage {
+            timestamp_ms: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+            source: kernel_message.source.node.clone(),
+            target: kernel_message.target.node.clone(),
+            dropped,
+            kernel_message: kernel_message.clone(),
+        })
+    }
+}
+
+struct Recorder {
+    msgpack_file: BufWriter<fs::File>,
+    jsonl_file: BufWriter<fs::File>,
+}
+
+/// Where the JSON Lines copy of a recording at `path` is written: `path`
+///  with `.jsonl` appended, so that it never clobbers the recording itself
+fn get_jsonl_path(path: &Path) -> PathBuf {
+    let mut jsonl_path = path.as_os_str().to_owned();
+    jsonl_path.push(".jsonl");
+    PathBuf::from(jsonl_path)
+}
+
+impl Recorder {
+    fn new(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(Recorder {
+            msgpack_file: BufWriter::new(fs::File::create(path)?),
+            jsonl_file: BufWriter::new(fs::File::create(get_jsonl_path(path))?),
+        })
+    }
+
+    fn record(&mut self, recorded_message: &RecordedMessage) -> anyhow::Result<()> {
+        self.msgpack_file.write_all(&rmp_serde::to_vec(recorded_message)?)?;
+        serde_json::to_writer(&mut self.jsonl_file, recorded_message)?;
+        self.jsonl_file.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.msgpack_file.flush()?;
+        self.jsonl_file.flush()?;
+        Ok(())
+    }
+}
+
+/// Read a recording written by the network router (msgpack format)
+#[instrument(level = "trace", err, skip_all)]
+pub fn read_recording(path: &Path) -> anyhow::Result<Vec<RecordedMessage>> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut recorded_messages = Vec::new();
+    while !reader.fill_buf()?.is_empty() {
+        recorded_messages.push(rmp_serde::from_read(&mut reader)?);
+    }
+    Ok(recorded_messages)
+}
+
 async fn handshake(stream: TcpStream) -> anyhow::Result<(String, WebSocketStream<TcpStream>)> {
     let ws_stream = accept_async(stream).await?;
     let (send_to_ws, mut recv_from_ws) = ws_stream.split();
@@ -69,24 +154,223 @@ async fn handle_connection(
     }
 }
 
+async fn handle_observer(
+    ws_stream: WebSocketStream<TcpStream>,
+    mut recv_in_observer: broadcast::Receiver<RecordedMessage>,
+) {
+    let (mut send_to_ws, mut recv_from_ws) = ws_stream.split();
+
+    loop {
+        tokio::select! {
+            recorded_message = recv_in_observer.recv() => {
+                match recorded_message {
+                    Ok(ref recorded_message) => {
+                        if let Err(e) = send_to_ws
+                            .send(Binary(rmp_serde::to_vec(recorded_message).unwrap()))
+                            .await
+                        {
+                            error!("Error sending message to observer: {}", e);
+                            break;
+                        }
+                    },
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("network_router: observer lagged; skipped {} messages\r", skipped);
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            },
+            message = recv_from_ws.next() => {
+                // Observers do not send messages: stop when they hang up
+                match message {
+                    Some(Ok(_)) => {},
+                    _ => break,
+                }
+            },
+        }
+    }
+}
+
+fn check_probability(name: &str, probability: f64) -> anyhow::Result<()> {
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(anyhow::anyhow!(
+            "network_router: {} probability must be in [0, 1]; got {}",
+            name,
+            probability,
+        ));
+    }
+    Ok(())
+}
+
+fn check_defects(defects: &Defects) -> anyhow::Result<()> {
+    if let Some(Latency { min_ms, max_ms }) = defects.latency {
+        if min_ms > max_ms {
+            return Err(anyhow::anyhow!(
+                "network_router: latency min_ms ({}) must not exceed max_ms ({})",
+                min_ms,
+                max_ms,
+            ));
+        }
+    }
+    if let Some(Dropping { probability }) = defects.dropping {
+        check_probability("dropping", probability)?;
+    }
+    if let Some(Reordering { probability, .. }) = defects.reordering {
+        check_probability("reordering", probability)?;
+    }
+    if let Some(Duplicating { probability }) = defects.duplicating {
+        check_probability("duplicating", probability)?;
+    }
+    Ok(())
+}
+
+fn check_links(partitions: &[Partition], links: &[Link]) -> anyhow::Result<()> {
+    for (i, partition) in partitions.iter().enumerate() {
+        check_schedule(&format!("partition {}", i), partition.start_secs, partition.end_secs)?;
+    }
+    for link in links {
+        let name = format!("link {} -> {}", link.source, link.target);
+        check_schedule(&name, link.start_secs, link.end_secs)?;
+        if let LinkAction::Drop { probability } = link.action {
+            check_probability(&name, probability)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_schedule(name: &str, start_secs: Option<u64>, end_secs: Option<u64>) -> anyhow::Result<()> {
+    if let (Some(start_secs), Some(end_secs)) = (start_secs, end_secs) {
+        if start_secs > end_secs {
+            return Err(anyhow::anyhow!(
+                "network_router: {} start_secs ({}) must not exceed end_secs ({})",
+                name,
+                start_secs,
+                end_secs,
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn is_active(start_secs: Option<u64>, end_secs: Option<u64>, elapsed: Duration) -> bool {
+    let elapsed = elapsed.as_secs();
+    start_secs.map(|s| elapsed >= s).unwrap_or(true)
+        && end_secs.map(|e| elapsed < e).unwrap_or(true)
+}
+
+fn is_partitioned(partition: &Partition, source: &str, target: &str) -> bool {
+    let find_group = |node: &str| partition.groups
+        .iter()
+        .position(|group| group.iter().any(|n| n == node));
+    match (find_group(source), find_group(target)) {
+        (Some(source_group), Some(target_group)) => source_group != target_group,
+        _ => false,
+    }
+}
+
+/// Apply active partitions & link rules to a message from `source` to
+///  `target`: returns the extra delay to add, or `None` if it is blocked
+fn apply_links(
+    partitions: &[Partition],
+    links: &[Link],
+    source: &str,
+    target: &str,
+    elapsed: Duration,
+    rng: &mut StdRng,
+) -> Option<Duration> {
+    let is_blocked = partitions
+        .iter()
+        .filter(|p| is_active(p.start_secs, p.end_secs, elapsed))
+        .any(|p| is_partitioned(p, source, target));
+    if is_blocked {
+        return None;
+    }
+    let mut delay = Duration::ZERO;
+    for link in links {
+        if link.source != source
+            || link.target != target
+            || !is_active(link.start_secs, link.end_secs, elapsed)
+        {
+            continue;
+        }
+        match link.action {
+            LinkAction::Block => return None,
+            LinkAction::Delay { ms } => delay += Duration::from_millis(ms),
+            LinkAction::Drop { probability } => {
+                if rng.gen_bool(probability) {
+                    return None;
+                }
+            },
+        }
+    }
+    Some(delay)
+}
+
+/// Decide what happens to a single message: returns one delay per copy
+///  to be delivered (empty if the message is dropped)
+fn apply_defects(defects: &Defects, rng: &mut StdRng) -> Vec<Duration> {
+    if let Some(Dropping { probability }) = defects.dropping {
+        if rng.gen_bool(probability) {
+            return vec![];
+        }
+    }
+    let copies = match defects.duplicating {
+        Some(Duplicating { probability }) if rng.gen_bool(probability) => 2,
+        _ => 1,
+    };
+    (0..copies)
+        .map(|_| {
+            let mut delay_ms = match defects.latency {
+                Some(Latency { min_ms, max_ms }) => rng.gen_range(min_ms..=max_ms),
+                None => 0,
+            };
+            if let Some(Reordering { probability, max_delay_ms }) = defects.reordering {
+                if rng.gen_bool(probability) {
+                    delay_ms += rng.gen_range(0..=max_delay_ms);
+                }
+            }
+            Duration::from_millis(delay_ms)
+        })
+        .collect()
+}
+
 #[instrument(level = "trace", err, skip_all)]
 pub async fn execute(
-    port: u16,
-    _defects: NetworkRouterDefects,
+    network_router: NetworkRouter,
     mut recv_kill_in_router: BroadcastRecvBool,
 ) -> anyhow::Result<()> {
+    let NetworkRouter { port, defects, seed, partitions, links, record } = network_router;
+    let defects: Defects = defects.into();
+    check_defects(&defects)?;
+    check_links(&partitions, &links)?;
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let start = Instant::now();
+    let mut recorder = match record {
+        Some(ref path) => Some(Recorder::new(path)?),
+        None => None,
+    };
+
     let (send_to_loop, mut recv_in_loop): (Sender, Receiver) = mpsc::channel(32);
+    let (send_to_observers, _) = broadcast::channel::<RecordedMessage>(256);
     let mut connections: Connections = HashMap::new();
 
     let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
 
     info!("network_router: online at {}\r", port);
+    info!("network_router: defects seed {}\r", seed);
+    if let Some(ref path) = record {
+        info!("network_router: recording to {:?}\r", path);
+    }
 
     loop {
         tokio::select! {
             Ok((stream, _)) = listener.accept() => {
                 let send_to_loop = send_to_loop.clone();
                 match handshake(stream).await {
+                    Ok((identifier, ws_stream)) if identifier.starts_with(OBSERVER_IDENTIFIER_PREFIX) => {
+                        info!("network_router: observer {} connected\r", identifier);
+                        task::spawn(handle_observer(ws_stream, send_to_observers.subscribe()));
+                    },
                     Ok((identifier, ws_stream)) => {
                         let (send_to_node, recv_in_node) = mpsc::channel(32);
                         let (send_to_kill_conn, recv_kill_in_conn) = mpsc::channel::<bool>(1);
@@ -102,14 +386,60 @@ pub async fn execute(
                 }
             },
             Some(kernel_message) = recv_in_loop.recv() => {
+                let link_delay = apply_links(
+                    &partitions,
+                    &links,
+                    &kernel_message.source.node,
+                    &kernel_message.target.node,
+                    start.elapsed(),
+                    &mut rng,
+                );
+                let delays = match link_delay {
+                    Some(link_delay) => apply_defects(&defects, &mut rng)
+                        .into_iter()
+                        .map(|delay| delay + link_delay)
+                        .collect(),
+                    None => vec![],
+                };
+                if recorder.is_some() || send_to_observers.receiver_count() > 0 {
+                    match RecordedMessage::new(&kernel_message, delays.is_empty()) {
+                        Ok(recorded_message) => {
+                            if let Some(ref mut recorder) = recorder {
+                                // Write out buffered messages whenever the router is idle
+                                let result = recorder.record(&recorded_message).and_then(|()| {
+                                    if recv_in_loop.is_empty() { recorder.flush() } else { Ok(()) }
+                                });
+                                if let Err(e) = result {
+                                    error!("network_router: error recording message: {}\r", e);
+                                }
+                            }
+                            let _ = send_to_observers.send(recorded_message);
+                        },
+                        Err(e) => error!("network_router: error recording message: {}\r", e),
+                    }
+                }
                 if let Some(Connection { send_to_node, .. }) = connections.get(&kernel_message.target.node) {
-                    let _ = send_to_node.send(kernel_message).await;
+                    for delay in delays {
+                        if delay.is_zero() {
+                            let _ = send_to_node.send(kernel_message.clone()).await;
+                        } else {
+                            let send_to_node = send_to_node.clone();
+                            let kernel_message = kernel_message.clone();
+                            task::spawn(async move {
+                                sleep(delay).await;
+                                let _ = send_to_node.send(kernel_message).await;
+                            });
+                        }
+                    }
                 }
             },
             _ = recv_kill_in_router.recv() => {
                 for connection in connections.values() {
                     let _ = connection.send_to_kill_conn.send(true).await;
                 }
+                if let Some(ref mut recorder) = recorder {
+                    recorder.flush()?;
+                }
                 break;
             },
         }
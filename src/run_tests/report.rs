@@ -0,0 +1,188 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde_json::json;
+use tracing::{info, instrument};
+
+use crate::run_tests::types::*;
+
+#[derive(Debug, Clone)]
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl FromStr for Report {
+    type Err = anyhow::Error;
+
+    /// Parse `<format>=<path>`, e.g. `junit=target/report.xml`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s.split_once('=').ok_or_else(|| anyhow::anyhow!(
+            "kit run-tests: --report must be of form `<format>=<path>`; got {}",
+            s,
+        ))?;
+        let format = match format {
+            "junit" => ReportFormat::Junit,
+            "json" => ReportFormat::Json,
+            _ => return Err(anyhow::anyhow!(
+                "kit run-tests: --report format must be `junit` or `json`; got {}",
+                format,
+            )),
+        };
+        Ok(Report { format, path: PathBuf::from(path) })
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.chars()
+        .filter(|c| matches!(c, '\t' | '\n' | '\r') || !c.is_control())
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn make_system_out(result: &TestResult) -> String {
+    let mut system_out = String::new();
+    for path in &result.setup_package_paths {
+        system_out.push_str(&format!("setup package: {}\n", path.display()));
+    }
+    for path in &result.test_package_paths {
+        system_out.push_str(&format!("test package: {}\n", path.display()));
+    }
+    for NodeLog { node, home, log } in &result.node_logs {
+        system_out.push_str(&format!("\n--- {} ({}) ---\n{}", node, home.display(), log));
+    }
+    system_out
+}
+
+fn make_junit(config_path: &str, results: &[TestResult]) -> String {
+    let failures = results
+        .iter()
+        .filter(|r| matches!(r.outcome, TestOutcome::Fail { .. }))
+        .count();
+    let errors = results
+        .iter()
+        .filter(|r| matches!(r.outcome, TestOutcome::Error { .. }))
+        .count();
+    let time: f64 = results.iter().map(|r| r.duration_secs).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"kit run-tests\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        results.len(), failures, errors, time,
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        escape_xml(config_path), results.len(), failures, errors, time,
+    ));
+    for result in results {
+        xml.push_str(&format!(
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&result.name),
+            escape_xml(config_path),
+            result.duration_secs,
+        ));
+        match result.outcome {
+            TestOutcome::Pass => {},
+            TestOutcome::Fail { ref test, ref file, line, column } => {
+                let location = format!("{} {}:{}:{}", test, file, line, column);
+                xml.push_str(&format!(
+                    "      <failure message=\"{}\" type=\"TesterFail\">{}</failure>\n",
+                    escape_xml(&location),
+                    escape_xml(&location),
+                ));
+            },
+            TestOutcome::Error { ref message } => {
+                xml.push_str(&format!(
+                    "      <error message=\"{}\">{}</error>\n",
+                    escape_xml(message.lines().next().unwrap_or_default()),
+                    escape_xml(message),
+                ));
+            },
+        }
+        xml.push_str(&format!(
+            "      <system-out>{}</system-out>\n",
+            escape_xml(&make_system_out(result)),
+        ));
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n");
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn write_report_file(path: &Path, content: &[u8]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Log a table of test names, outcomes, & durations
+pub fn log_summary(results: &[TestResult]) {
+    let name_width = results
+        .iter()
+        .map(|r| r.name.len())
+        .chain(std::iter::once("TEST".len()))
+        .max()
+        .unwrap_or_default();
+    let mut summary = format!("{:<name_width$}  RESULT  TIME\n", "TEST");
+    for result in results {
+        let (outcome, detail) = match result.outcome {
+            TestOutcome::Pass => ("PASS", String::new()),
+            TestOutcome::Fail { ref test, ref file, line, column } => {
+                ("FAIL", format!("  {} {}:{}:{}", test, file, line, column))
+            },
+            TestOutcome::Error { ref message } => {
+                ("ERROR", format!("  {}", message.lines().next().unwrap_or_default()))
+            },
+        };
+        summary.push_str(&format!(
+            "{:<name_width$}  {:<6}  {:.1}s{}\n",
+            result.name,
+            outcome,
+            result.duration_secs,
+            detail,
+        ));
+    }
+    let num_passed = results
+        .iter()
+        .filter(|r| matches!(r.outcome, TestOutcome::Pass))
+        .count();
+    summary.push_str(&format!("{} passed; {} failed", num_passed, results.len() - num_passed));
+    info!("\n{}", summary);
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub fn write_reports(
+    reports: &[Report],
+    config_path: &str,
+    results: &[TestResult],
+) -> anyhow::Result<()> {
+    for Report { format, path } in reports {
+        let content = match format {
+            ReportFormat::Junit => make_junit(config_path, results).into_bytes(),
+            ReportFormat::Json => serde_json::to_vec_pretty(&json!({
+                "config": config_path,
+                "results": results,
+            }))?,
+        };
+        write_report_file(path, &content)?;
+        info!("Wrote {:?} report to {:?}.", format, path);
+    }
+    Ok(())
+}
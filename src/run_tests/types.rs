@@ -23,6 +23,9 @@ pub enum Runtime {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Test {
+    /// Name used in logs & reports [default: comma-separated test package names]
+    #[serde(default)]
+    pub name: Option<String>,
     pub setup_package_paths: Vec<PathBuf>,
     pub test_packages: Vec<TestPackage>,
     pub package_build_verbose: bool,
@@ -40,13 +43,134 @@ pub struct TestPackage {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkRouter {
     pub port: u16,
+    #[serde(default)]
     pub defects: NetworkRouterDefects,
+    /// Seed for the defects RNG; if unset, a random seed is chosen and logged
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub partitions: Vec<Partition>,
+    #[serde(default)]
+    pub links: Vec<Link>,
+    /// If set, record all routed messages to this path (msgpack) and
+    ///  to the same path with `.jsonl` appended (JSON Lines)
+    #[serde(default)]
+    pub record: Option<PathBuf>,
 }
 
+impl NetworkRouter {
+    pub fn new(port: u16) -> Self {
+        NetworkRouter {
+            port,
+            defects: NetworkRouterDefects::None,
+            seed: None,
+            partitions: vec![],
+            links: vec![],
+            record: None,
+        }
+    }
+}
+
+/// Nodes in different `groups` cannot reach each other while the
+///  partition is active; nodes not listed in any group are unaffected.
+///  `start_secs`/`end_secs` are measured from network router start
+///  and default to "always active".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Partition {
+    pub groups: Vec<Vec<String>>,
+    #[serde(default)]
+    pub start_secs: Option<u64>,
+    #[serde(default)]
+    pub end_secs: Option<u64>,
+}
+
+/// A rule applied to messages sent from `source` to `target` (directed)
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Link {
+    pub source: String,
+    pub target: String,
+    pub action: LinkAction,
+    #[serde(default)]
+    pub start_secs: Option<u64>,
+    #[serde(default)]
+    pub end_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LinkAction {
+    Block,
+    Delay { ms: u64 },
+    Drop { probability: f64 },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum NetworkRouterDefects {
+    #[default]
     None,
-    // TODO: add Latency, Dropping, ..., All
+    Latency(Latency),
+    Dropping(Dropping),
+    Reordering(Reordering),
+    Duplicating(Duplicating),
+    All(Defects),
+}
+
+/// Per-message delay, uniformly sampled from `[min_ms, max_ms]`;
+///  set `min_ms == max_ms` for a fixed delay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Latency {
+    pub min_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dropping {
+    pub probability: f64,
+}
+
+/// With `probability`, hold a message back for up to `max_delay_ms`
+///  so that later messages overtake it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reordering {
+    pub probability: f64,
+    pub max_delay_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Duplicating {
+    pub probability: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Defects {
+    pub latency: Option<Latency>,
+    pub dropping: Option<Dropping>,
+    pub reordering: Option<Reordering>,
+    pub duplicating: Option<Duplicating>,
+}
+
+impl From<NetworkRouterDefects> for Defects {
+    fn from(defects: NetworkRouterDefects) -> Self {
+        match defects {
+            NetworkRouterDefects::None => Defects::default(),
+            NetworkRouterDefects::Latency(latency) => Defects {
+                latency: Some(latency),
+                ..Defects::default()
+            },
+            NetworkRouterDefects::Dropping(dropping) => Defects {
+                dropping: Some(dropping),
+                ..Defects::default()
+            },
+            NetworkRouterDefects::Reordering(reordering) => Defects {
+                reordering: Some(reordering),
+                ..Defects::default()
+            },
+            NetworkRouterDefects::Duplicating(duplicating) => Defects {
+                duplicating: Some(duplicating),
+                ..Defects::default()
+            },
+            NetworkRouterDefects::All(defects) => defects,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,6 +182,61 @@ pub struct Node {
     pub rpc: Option<String>,
     pub runtime_verbose: bool,
     pub is_testnet: bool,
+    /// Start from this `kit snapshot` rather than from a fresh node
+    #[serde(default)]
+    pub snapshot: Option<String>,
+}
+
+impl Test {
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            self.test_packages
+                .iter()
+                .map(|tp| tp.path.file_name().unwrap_or_default().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("FAIL: {test} {file}:{line}:{column}")]
+pub struct TestFailure {
+    pub test: String,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum TestOutcome {
+    Pass,
+    Fail {
+        test: String,
+        file: String,
+        line: u32,
+        column: u32,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeLog {
+    pub node: String,
+    pub home: PathBuf,
+    pub log: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    pub duration_secs: f64,
+    pub outcome: TestOutcome,
+    pub setup_package_paths: Vec<PathBuf>,
+    pub test_package_paths: Vec<PathBuf>,
+    pub node_logs: Vec<NodeLog>,
 }
 
 pub type NodeHandles = Arc<Mutex<Vec<Child>>>;
@@ -108,7 +108,7 @@ fn check_python_venv(python: &str) -> anyhow::Result<()> {
 }
 
 #[instrument(level = "trace", err, skip_all)]
-fn is_command_installed(cmd: &str) -> anyhow::Result<bool> {
+pub fn is_command_installed(cmd: &str) -> anyhow::Result<bool> {
     Ok(Command::new("which")
         .arg(cmd)
         .stdout(Stdio::null())
@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tracing::{info, instrument};
+use walkdir::WalkDir;
+
+use super::boot_fake_node::NODE_LOG_FILE_NAME;
+use super::build;
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+/// Node state wiped by `cleanup` and replaced on restore
+pub const NODE_STATE_DIRS: [&str; 4] = ["kernel", "kv", "sqlite", "vfs"];
+
+fn get_snapshot_dir(name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(anyhow::anyhow!("kit snapshot: invalid snapshot name {:?}", name));
+    }
+    Ok(PathBuf::from(build::CACHE_DIR).join(SNAPSHOTS_DIR).join(name))
+}
+
+/// Recursively copy `source` into `target`, skipping the kit node log
+fn copy_dir(source: &Path, target: &Path) -> anyhow::Result<()> {
+    for entry in WalkDir::new(source) {
+        let entry = entry?;
+        let relative_path = entry.path().strip_prefix(source)?;
+        if relative_path == Path::new(NODE_LOG_FILE_NAME) {
+            continue;
+        }
+        let target_path = target.join(relative_path);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target_path)?;
+        } else {
+            fs::copy(entry.path(), &target_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove the node state under `home`, leaving its other contents in place
+pub fn remove_node_state(home: &Path) -> anyhow::Result<()> {
+    for dir in &NODE_STATE_DIRS {
+        let dir = home.join(dir);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy the node home at `home` into the snapshot `name`, replacing any
+///  existing snapshot of that name; the node should not be running
+#[instrument(level = "trace", err, skip_all)]
+pub fn save(home: &Path, name: &str) -> anyhow::Result<()> {
+    if !home.join("kernel").exists() {
+        return Err(anyhow::anyhow!(
+            "kit snapshot: {:?} is not a node home (no kernel directory)",
+            home,
+        ));
+    }
+    let snapshot_dir = get_snapshot_dir(name)?;
+    if snapshot_dir.exists() {
+        fs::remove_dir_all(&snapshot_dir)?;
+    }
+    copy_dir(home, &snapshot_dir)?;
+    info!("Saved {:?} to snapshot {} at {:?}.", home, name, snapshot_dir);
+    Ok(())
+}
+
+/// Replace the node state at `home` with that of the snapshot `name`
+#[instrument(level = "trace", err, skip_all)]
+pub fn restore(name: &str, home: &Path) -> anyhow::Result<()> {
+    let snapshot_dir = get_snapshot_dir(name)?;
+    if !snapshot_dir.exists() {
+        return Err(anyhow::anyhow!(
+            "kit snapshot: no snapshot named {}; see `kit snapshot list`",
+            name,
+        ));
+    }
+    fs::create_dir_all(home)?;
+    remove_node_state(home)?;
+    copy_dir(&snapshot_dir, home)?;
+    info!("Restored snapshot {} to {:?}.", name, home);
+    Ok(())
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub fn list() -> anyhow::Result<()> {
+    let snapshots_dir = PathBuf::from(build::CACHE_DIR).join(SNAPSHOTS_DIR);
+    let mut names = Vec::new();
+    if snapshots_dir.exists() {
+        for entry in fs::read_dir(&snapshots_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+    names.sort();
+    if names.is_empty() {
+        info!("No snapshots.");
+    }
+    for name in names {
+        info!("{}", name);
+    }
+    Ok(())
+}
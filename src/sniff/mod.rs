@@ -0,0 +1,100 @@
+use futures_util::{SinkExt, StreamExt};
+use kinode_process_lib::kernel_types as kt;
+use kinode_process_lib::Address;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message::{Binary, Text}};
+use tracing::{info, instrument};
+
+use super::run_tests::network_router::{RecordedMessage, OBSERVER_IDENTIFIER_PREFIX};
+
+pub struct Filters {
+    pub nodes: Vec<String>,
+    pub processes: Vec<String>,
+    pub packages: Vec<String>,
+}
+
+impl Filters {
+    fn address_matches(&self, address: &Address) -> (bool, bool, bool) {
+        (
+            self.nodes.iter().any(|n| n == &address.node),
+            self.processes.iter().any(|p| p == address.process.process()),
+            self.packages.iter().any(|p| p == address.process.package()),
+        )
+    }
+
+    /// Each non-empty filter must be matched by either the source or the target
+    fn matches(&self, recorded_message: &RecordedMessage) -> bool {
+        let (source_node, source_process, source_package) =
+            self.address_matches(&recorded_message.kernel_message.source);
+        let (target_node, target_process, target_package) =
+            self.address_matches(&recorded_message.kernel_message.target);
+        (self.nodes.is_empty() || source_node || target_node)
+            && (self.processes.is_empty() || source_process || target_process)
+            && (self.packages.is_empty() || source_package || target_package)
+    }
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    if let Ok(json) = serde_json::from_slice::<serde_json::Value>(bytes) {
+        json.to_string()
+    } else if let Ok(s) = std::str::from_utf8(bytes) {
+        format!("{:?}", s)
+    } else {
+        format!("<{} bytes>", bytes.len())
+    }
+}
+
+fn format_recorded_message(recorded_message: &RecordedMessage) -> String {
+    let kernel_message = &recorded_message.kernel_message;
+    let (kind, body) = match kernel_message.message {
+        kt::Message::Request(ref request) => (
+            format!("Request (expects_response: {:?})", request.expects_response),
+            format_bytes(&request.body),
+        ),
+        kt::Message::Response((ref response, _)) => (
+            "Response".to_string(),
+            format_bytes(&response.body),
+        ),
+    };
+    let rsvp = kernel_message.rsvp
+        .as_ref()
+        .map(|r| r.to_string())
+        .unwrap_or_else(|| "None".to_string());
+    let blob = kernel_message.lazy_load_blob
+        .as_ref()
+        .map(|b| format!("{} bytes", b.bytes.len()))
+        .unwrap_or_else(|| "None".to_string());
+    format!(
+        "{}{} -> {}\n  id: {}\n  rsvp: {}\n  {}\n  body: {}\n  blob: {}",
+        if recorded_message.dropped { "[DROPPED] " } else { "" },
+        kernel_message.source,
+        kernel_message.target,
+        kernel_message.id,
+        rsvp,
+        kind,
+        body,
+        blob,
+    )
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub async fn execute(network_router_port: u16, filters: Filters) -> anyhow::Result<()> {
+    let (ws_stream, _) = connect_async(
+        format!("ws://127.0.0.1:{}", network_router_port),
+    ).await?;
+    let (mut send_to_ws, mut recv_from_ws) = ws_stream.split();
+    send_to_ws.send(Text(format!("{}-{}", OBSERVER_IDENTIFIER_PREFIX, std::process::id()))).await?;
+
+    info!("Sniffing network router on port {}...", network_router_port);
+
+    while let Some(message) = recv_from_ws.next().await {
+        if let Binary(ref bin) = message? {
+            let recorded_message: RecordedMessage = rmp_serde::from_slice(bin)?;
+            if filters.matches(&recorded_message) {
+                info!("{}", format_recorded_message(&recorded_message));
+            }
+        }
+    }
+
+    info!("Network router closed connection.");
+    Ok(())
+}
@@ -1,25 +1,31 @@
 use std::fs;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use tracing::{info, instrument};
 use walkdir::WalkDir;
 use zip::write::FileOptions;
 
+use super::build;
+use super::check;
 use super::inject_message;
 
+/// Add the zipped package at `bytes_path` to the app store, or update it,
+///  mirroring it to other nodes if `mirror`
 #[instrument(level = "trace", err, skip_all)]
-fn new_package(
+pub fn new_package(
     node: Option<&str>,
     package_name: &str,
     publisher_node: &str,
     bytes_path: &str,
+    mirror: bool,
 ) -> anyhow::Result<serde_json::Value> {
     let message = json!({
         "NewPackage": {
             "package": {"package_name": package_name, "publisher_node": publisher_node},
-            "mirror": true
+            "mirror": mirror
         }
     });
 
@@ -57,32 +63,42 @@ pub fn interact_with_package(
     )
 }
 
+/// Zip `directory` reproducibly: entries sorted by name, with fixed
+///  timestamps & permissions, so that the same contents give the same zip
 #[instrument(level = "trace", err, skip_all)]
 fn zip_directory(directory: &Path, zip_filename: &str) -> anyhow::Result<()> {
     let file = fs::File::create(zip_filename)?;
-    let walkdir = WalkDir::new(directory);
+    let walkdir = WalkDir::new(directory).sort_by_file_name();
     let it = walkdir.into_iter();
 
     let mut zip = zip::ZipWriter::new(file);
 
     let options = FileOptions::default()
         .compression_method(zip::CompressionMethod::Stored)
-        .unix_permissions(0o755);
+        .last_modified_time(zip::DateTime::default());
+    let file_options = options.unix_permissions(0o644);
+    let dir_options = options.unix_permissions(0o755);
 
     for entry in it {
         let entry = entry?;
         let path = entry.path();
         let name = path.strip_prefix(Path::new(directory))?;
+        // Zip paths use `/` on all platforms
+        let name = name
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
 
         if path.is_file() {
-            zip.start_file(name.to_string_lossy(), options)?;
+            zip.start_file(name, file_options)?;
             let mut f = fs::File::open(path)?;
             let mut buffer = Vec::new();
             f.read_to_end(&mut buffer)?;
-            zip.write_all(&*buffer)?;
-        } else if name.as_os_str().len() != 0 {
+            zip.write_all(&buffer)?;
+        } else if !name.is_empty() {
             // Only if it is not the root directory
-            zip.add_directory(name.to_string_lossy(), options)?;
+            zip.add_directory(name, dir_options)?;
         }
     }
 
@@ -90,29 +106,63 @@ fn zip_directory(directory: &Path, zip_filename: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Where `start-package` writes the zip of the package: `target/<package>:<publisher>.zip`
+pub fn get_zip_path(package_dir: &Path, metadata: &check::Metadata) -> PathBuf {
+    package_dir
+        .join("target")
+        .join(format!("{}:{}", metadata.package, metadata.publisher))
+        .with_extension("zip")
+}
+
+/// Zip the package's `pkg/` to `zip_path`, writing its metadata and
+///  `pkg/build-info.json` (if built) along with the hash of the zip beside
+///  it, with the extension `.build-info.json`; returns the hash
 #[instrument(level = "trace", err, skip_all)]
-pub async fn execute(package_dir: &Path, url: &str) -> anyhow::Result<()> {
-    if !package_dir.join("pkg").exists() {
-        return Err(anyhow::anyhow!(
-            "Required `pkg/` dir not found within given input dir {:?} (or cwd, if none given). Please re-run targeting a package.",
-            package_dir,
-        ));
-    }
+pub fn zip_package(
+    package_dir: &Path,
+    metadata: &check::Metadata,
+    zip_path: &Path,
+) -> anyhow::Result<String> {
     let pkg_dir = package_dir.join("pkg").canonicalize()?;
-    let metadata: serde_json::Value = serde_json::from_reader(fs::File::open(
-        pkg_dir.join("metadata.json")
-    )?)?;
-    let package_name = metadata["package"].as_str().unwrap();
-    let publisher = metadata["publisher"].as_str().unwrap();
+    if let Some(parent) = zip_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    zip_directory(&pkg_dir, zip_path.to_str().unwrap())?;
+    let zip_hash = hex::encode(Sha256::digest(fs::read(zip_path)?));
+
+    let mut build_info = match fs::read_to_string(package_dir.join(build::BUILD_INFO_PATH)) {
+        Ok(build_info) => serde_json::from_str(&build_info)?,
+        Err(_) => json!({}),
+    };
+    let source_info = serde_json::to_value(build::SourceInfo::new(package_dir))?;
+    if let (Some(build_info), Some(source_info)) = (build_info.as_object_mut(), source_info.as_object()) {
+        build_info.extend(source_info.clone());
+    }
+    build_info["package"] = json!(metadata.package);
+    build_info["publisher"] = json!(metadata.publisher);
+    build_info["version"] = json!(metadata.version);
+    build_info["zip"] = json!({
+        "file": zip_path.file_name().unwrap().to_string_lossy(),
+        "sha256": zip_hash,
+    });
+    fs::write(
+        zip_path.with_extension("build-info.json"),
+        format!("{}\n", serde_json::to_string_pretty(&build_info)?),
+    )?;
+    Ok(zip_hash)
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub async fn execute(package_dir: &Path, url: &str) -> anyhow::Result<()> {
+    let metadata = check::validate(package_dir)?;
+    let package_name = metadata.package.as_str();
+    let publisher = metadata.publisher.as_str();
     let pkg_publisher = format!("{}:{}", package_name, publisher);
     info!("{}", pkg_publisher);
 
     // Create zip and put it in /target
-    let parent_dir = pkg_dir.parent().unwrap();
-    let target_dir = parent_dir.join("target");
-    fs::create_dir_all(&target_dir)?;
-    let zip_filename = target_dir.join(&pkg_publisher).with_extension("zip");
-    zip_directory(&pkg_dir, &zip_filename.to_str().unwrap())?;
+    let zip_filename = get_zip_path(package_dir, &metadata);
+    zip_package(package_dir, &metadata, &zip_filename)?;
 
     // Create and send new package request
     let new_pkg_request = new_package(
@@ -120,6 +170,7 @@ pub async fn execute(package_dir: &Path, url: &str) -> anyhow::Result<()> {
         package_name,
         publisher,
         zip_filename.to_str().unwrap(),
+        true,
     )?;
     let response = inject_message::send_request(url, new_pkg_request).await?;
     let inject_message::Response { ref body, .. } = inject_message::parse_response(response).await?;
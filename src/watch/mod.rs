@@ -0,0 +1,156 @@
+use std::path::{Component, Path};
+use std::time::{Duration, Instant};
+
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tracing::{error, info, instrument, warn};
+
+use super::build;
+use super::start_package;
+
+/// Wait for this long without changes before rebuilding
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// Files written by builds that would otherwise trigger another cycle
+const IGNORED_FILE_NAMES: [&str; 2] = ["Cargo.lock", "package-lock.json"];
+
+/// What a batch of file changes requires
+#[derive(Debug, Default)]
+struct Changes {
+    ui: bool,
+    processes: bool,
+    manifest: bool,
+}
+
+impl Changes {
+    fn all() -> Self {
+        Changes { ui: true, processes: true, manifest: true }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.ui && !self.processes && !self.manifest
+    }
+
+    /// Record the change to `relative_path` (relative to the package dir),
+    ///  ignoring build outputs, installed dependencies & hidden/backup files
+    fn add(&mut self, relative_path: &Path) {
+        let components: Vec<&str> = relative_path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(c) => c.to_str(),
+                _ => None,
+            })
+            .collect();
+        let Some(file_name) = components.last() else {
+            return;
+        };
+        if file_name.starts_with('.')
+            || file_name.ends_with('~')
+            || IGNORED_FILE_NAMES.contains(file_name)
+            || components.iter().any(|c| c.starts_with('.') || build::FINGERPRINT_IGNORED_DIRS.contains(c))
+        {
+            return;
+        }
+        match components.as_slice() {
+            ["ui", "dist", ..] => {},
+            ["ui", ..] => self.ui = true,
+            // `kit` build dependencies may have changed
+            ["pkg", "metadata.json"] => self.processes = true,
+            ["pkg", "manifest.json"] => self.manifest = true,
+            // Build outputs
+            ["pkg", ..] => {},
+            _ => self.processes = true,
+        }
+    }
+}
+
+/// Rebuild what `changes` requires and reinstall
+async fn rebuild_and_start(
+    package_dir: &Path,
+    changes: &Changes,
+    no_ui: bool,
+    verbose: bool,
+    skip_deps_check: bool,
+    options: &build::BuildOptions,
+    url: &str,
+) -> anyhow::Result<()> {
+    let is_build_ui = changes.ui && !no_ui && package_dir.join("ui").exists();
+    if is_build_ui || changes.processes {
+        build::execute(
+            package_dir,
+            !is_build_ui,
+            !changes.processes,
+            verbose,
+            skip_deps_check,
+            options,
+        ).await?;
+    }
+    start_package::execute(package_dir, url).await
+}
+
+/// Build & start the package, then rebuild & restart it whenever its
+///  sources, `wit/`, `pkg/manifest.json` or `ui/` change, until killed
+#[instrument(level = "trace", err, skip_all)]
+pub async fn execute(
+    package_dir: &Path,
+    no_ui: bool,
+    verbose: bool,
+    url: &str,
+    skip_deps_check: bool,
+    options: &build::BuildOptions,
+) -> anyhow::Result<()> {
+    if !package_dir.join("pkg").exists() {
+        return Err(anyhow::anyhow!(
+            "Required `pkg/` dir not found within given input dir {:?} (or cwd, if none given). Please re-run targeting a package.",
+            package_dir,
+        ));
+    }
+    let package_dir = package_dir.canonicalize()?;
+
+    let (send_event, mut recv_event) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let _ = send_event.send(event);
+    })?;
+    watcher.watch(&package_dir, RecursiveMode::Recursive)?;
+
+    let mut changes = Changes::all();
+    // Dependencies need only be checked on the first build
+    let mut skip_deps_check = skip_deps_check;
+    loop {
+        let start = Instant::now();
+        match rebuild_and_start(&package_dir, &changes, no_ui, verbose, skip_deps_check, options, url).await {
+            Ok(()) => info!(
+                "kit watch: built and started {:?} in {:.1}s; watching for changes...",
+                package_dir,
+                start.elapsed().as_secs_f32(),
+            ),
+            Err(e) => error!("kit watch: failed: {:?}\nwatching for changes...", e),
+        }
+        skip_deps_check = true;
+
+        changes = Changes::default();
+        while changes.is_empty() {
+            let Some(event) = recv_event.recv().await else {
+                return Err(anyhow::anyhow!("kit watch: file watcher stopped"));
+            };
+            add_event(&package_dir, event, &mut changes);
+            // Debounce: gather further changes until there is a lull
+            while let Ok(Some(event)) = tokio::time::timeout(DEBOUNCE, recv_event.recv()).await {
+                add_event(&package_dir, event, &mut changes);
+            }
+        }
+    }
+}
+
+fn add_event(package_dir: &Path, event: notify::Result<notify::Event>, changes: &mut Changes) {
+    match event {
+        Ok(event) if event.kind.is_access() => {},
+        Ok(event) => {
+            for path in event.paths {
+                if let Ok(relative_path) = path.strip_prefix(package_dir) {
+                    changes.add(relative_path);
+                }
+            }
+        },
+        Err(e) => warn!("kit watch: error watching files: {}", e),
+    }
+}
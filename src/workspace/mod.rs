@@ -0,0 +1,162 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tracing::{info, instrument};
+
+use super::build;
+use super::config::{KitConfig, PROJECT_CONFIG_FILE_NAME};
+use super::start_package;
+
+#[derive(Debug)]
+pub struct Member {
+    pub name: String,
+    pub package_dir: PathBuf,
+    pub dependencies: Vec<String>,
+}
+
+/// Packages listed under `[workspace]` in a root `kit.toml`
+#[derive(Debug)]
+pub struct Workspace {
+    pub members: Vec<Member>,
+}
+
+/// The workspace rooted at `dir`, if `dir` is not itself a package and
+///  has a `kit.toml` listing `[workspace]` members
+#[instrument(level = "trace", err, skip_all)]
+pub fn find(dir: &Path) -> anyhow::Result<Option<Workspace>> {
+    let config_path = dir.join(PROJECT_CONFIG_FILE_NAME);
+    if dir.join("pkg").exists() || !config_path.is_file() {
+        return Ok(None);
+    }
+    let config: KitConfig = toml::from_str(&fs::read_to_string(&config_path)?)
+        .map_err(|e| anyhow::anyhow!("kit: couldn't parse config {:?}: {}", config_path, e))?;
+    if config.workspace.members.is_empty() {
+        return Ok(None);
+    }
+
+    let mut dependencies = config.workspace.dependencies;
+    let mut members = Vec::new();
+    for member_path in config.workspace.members {
+        let package_dir = dir.join(&member_path);
+        let name = package_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow::anyhow!("kit: bad workspace member {:?}", member_path))?;
+        if members.iter().any(|m: &Member| m.name == name) {
+            return Err(anyhow::anyhow!("kit: workspace has two members named {}", name));
+        }
+        members.push(Member {
+            dependencies: dependencies.remove(&name).unwrap_or_default(),
+            name,
+            package_dir,
+        });
+    }
+    if let Some(name) = dependencies.keys().next() {
+        return Err(anyhow::anyhow!(
+            "kit: workspace dependencies given for {}, which is not a member",
+            name,
+        ));
+    }
+    for member in &members {
+        for dependency in &member.dependencies {
+            if !members.iter().any(|m| &m.name == dependency) {
+                return Err(anyhow::anyhow!(
+                    "kit: workspace member {} depends on {}, which is not a member",
+                    member.name,
+                    dependency,
+                ));
+            }
+        }
+    }
+    Ok(Some(Workspace { members }))
+}
+
+impl Workspace {
+    /// Members named in `names` (all members if empty), dependencies
+    ///  before dependents; dependencies not named are not included
+    pub fn select(self, names: &[String]) -> anyhow::Result<Vec<Member>> {
+        for name in names {
+            if !self.members.iter().any(|m| &m.name == name) {
+                return Err(anyhow::anyhow!(
+                    "kit: no workspace member named {}; members are: {}",
+                    name,
+                    self.members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>().join(", "),
+                ));
+            }
+        }
+        let mut selected: Vec<Member> = self.members
+            .into_iter()
+            .filter(|m| names.is_empty() || names.contains(&m.name))
+            .collect();
+
+        // Topological sort, otherwise keeping the order of `members`
+        let selected_names: HashSet<String> = selected.iter().map(|m| m.name.clone()).collect();
+        let mut remaining_dependencies: HashMap<String, usize> = selected
+            .iter()
+            .map(|m| (
+                m.name.clone(),
+                m.dependencies.iter().filter(|d| selected_names.contains(*d)).count(),
+            ))
+            .collect();
+        let mut ordered: Vec<Member> = Vec::new();
+        while !selected.is_empty() {
+            let next = selected
+                .iter()
+                .position(|m| remaining_dependencies.get(&m.name) == Some(&0))
+                .ok_or_else(|| anyhow::anyhow!(
+                    "kit: workspace dependencies have a cycle among: {}",
+                    selected.iter().map(|m| m.name.as_str()).collect::<Vec<_>>().join(", "),
+                ))?;
+            let next = selected.remove(next);
+            remaining_dependencies.remove(&next.name);
+            for member in &selected {
+                if member.dependencies.contains(&next.name) {
+                    if let Some(count) = remaining_dependencies.get_mut(&member.name) {
+                        *count -= 1;
+                    }
+                }
+            }
+            ordered.push(next);
+        }
+        Ok(ordered)
+    }
+}
+
+/// The members named in `names` (all if empty) of the workspace rooted at `dir`, in
+///  dependency order, or `None` if `dir` is not a workspace root
+#[instrument(level = "trace", err, skip_all)]
+pub fn find_members(dir: &Path, names: &[String]) -> anyhow::Result<Option<Vec<Member>>> {
+    match find(dir)? {
+        Some(workspace) => Ok(Some(workspace.select(names)?)),
+        None if names.is_empty() => Ok(None),
+        None => Err(anyhow::anyhow!(
+            "kit: --member given, but {:?} is not a workspace (no `[workspace]` members in its {})",
+            dir,
+            PROJECT_CONFIG_FILE_NAME,
+        )),
+    }
+}
+
+#[instrument(level = "trace", err, skip_all)]
+pub async fn build(
+    members: &[Member],
+    no_ui: bool,
+    ui_only: bool,
+    verbose: bool,
+    skip_deps_check: bool,
+    options: &build::BuildOptions,
+) -> anyhow::Result<()> {
+    let package_dirs: Vec<PathBuf> = members.iter().map(|m| m.package_dir.clone()).collect();
+    build::execute_packages(&package_dirs, no_ui, ui_only, verbose, skip_deps_check, options).await
+}
+
+/// Start `members`, which must be in dependency order
+#[instrument(level = "trace", err, skip_all)]
+pub async fn start(members: &[Member], url: &str) -> anyhow::Result<()> {
+    for member in members {
+        info!("Starting workspace member {}...", member.name);
+        start_package::execute(&member.package_dir, url).await?;
+    }
+    Ok(())
+}